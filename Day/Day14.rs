@@ -3,7 +3,11 @@ fn main() {
     let input = std::fs::read_to_string("Day7.txt").unwrap();
 
     println!("Part 1: {}", solve(&input));
-    part2(&input);
+    let step = part2(&input);
+    println!("Part 2: {}", step);
+    if std::env::args().any(|arg| arg == "--frame") {
+        print!("{}", render(&parse_input(&input), step));
+    }
 }
 
 type PosType = i16;
@@ -36,6 +40,13 @@ impl Robot {
             self.y -= HEIGHT;
         }
     }
+
+    fn position_after(&self, steps: usize) -> (PosType, PosType) {
+        let steps = steps as i64;
+        let x = (self.x as i64 + self.vx as i64 * steps).rem_euclid(WIDTH as i64);
+        let y = (self.y as i64 + self.vy as i64 * steps).rem_euclid(HEIGHT as i64);
+        (x as PosType, y as PosType)
+    }
 }
 
 fn solve(input: &String) -> usize {
@@ -69,29 +80,50 @@ fn solve(input: &String) -> usize {
     safety_factor
 }
 
-// THIS IS NOT A SOLUTION
-// this is merely a tool to help find it
-// this finds the first time at which no two robots occupy the same space
-fn part2(input: &String) {
-    let mut robots = parse_input(&input);
+/// Spread of the values around their mean, scaled by `n²` to stay in integers.
+fn spread(values: impl Iterator<Item = PosType>) -> i64 {
+    let (mut n, mut sum, mut sum_sq) = (0i64, 0i64, 0i64);
+    for v in values {
+        n += 1;
+        sum += v as i64;
+        sum_sq += v as i64 * v as i64;
+    }
+    n * sum_sq - sum * sum
+}
 
-    let mut s = 0;
-    'outer: loop {
-        s += 1;
-        for robot in &mut robots {
-            robot.move_once();
-        }
+/// Finds the step at which the robots draw the Christmas tree.
+///
+/// Robot x positions repeat every `WIDTH` steps and y positions every `HEIGHT`
+/// steps. The tree is the frame where the robots bunch up, so we take the step
+/// of least x spread within one x period and of least y spread within one y
+/// period, and combine them with the Chinese remainder theorem.
+fn part2(input: &String) -> usize {
+    let robots = parse_input(input);
+
+    let tx = (0..WIDTH as usize)
+        .min_by_key(|&t| spread(robots.iter().map(|r| r.position_after(t).0)))
+        .unwrap();
+    let ty = (0..HEIGHT as usize)
+        .min_by_key(|&t| spread(robots.iter().map(|r| r.position_after(t).1)))
+        .unwrap();
+
+    (0..HEIGHT as usize)
+        .map(|k| tx + k * WIDTH as usize)
+        .find(|t| t % HEIGHT as usize == ty)
+        .expect("WIDTH and HEIGHT are coprime")
+}
 
-        for i in 0..(robots.len() - 1) {
-            for j in (i + 1)..robots.len() {
-                if robots[i].x == robots[j].x && robots[i].y == robots[j].y {
-                    continue 'outer;
-                }
-            }
-        }
-        println!("s: {s}");
-        break;
+/// Draws the robots after `steps` steps, one character per tile.
+fn render(robots: &[Robot], steps: usize) -> String {
+    let mut grid = vec![vec!['.'; WIDTH as usize]; HEIGHT as usize];
+    for robot in robots {
+        let (x, y) = robot.position_after(steps);
+        grid[y as usize][x as usize] = '#';
     }
+
+    grid.into_iter()
+        .map(|row| row.into_iter().chain(std::iter::once('\n')).collect::<String>())
+        .collect()
 }
 
 fn parse_input(input: &String) -> Vec<Robot> {
@@ -109,3 +141,43 @@ fn parse_input(input: &String) -> Vec<Robot> {
         })
         .collect::<Vec<_>>()
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_part2_finds_clustered_frame() {
+        const STEP: usize = 6_543;
+
+        // Where each robot is at STEP, and its velocity: a 3x3 block of robots
+        // and four outliers. The input winds them back STEP steps.
+        let at_step = [
+            (50, 50, 37, -71), (51, 50, -83, 29), (52, 50, 64, 55),
+            (50, 51, -46, 91), (51, 51, 88, -19), (52, 51, -25, -97),
+            (50, 52, 13, 76), (51, 52, -59, -38), (52, 52, 72, 44),
+            (38, 38, 21, -63), (64, 38, -96, 14), (38, 64, 49, 83), (64, 64, -17, -52),
+        ];
+        let input: String = at_step
+            .iter()
+            .map(|&(x, y, vx, vy)| {
+                let x0 = (x - vx * STEP as i64).rem_euclid(WIDTH as i64);
+                let y0 = (y - vy * STEP as i64).rem_euclid(HEIGHT as i64);
+                format!("p={},{} v={},{}\n", x0, y0, vx, vy)
+            })
+            .collect();
+
+        // Brute force: the frame of least spread over a whole cycle.
+        let robots = parse_input(&input);
+        let brute = (0..WIDTH as usize * HEIGHT as usize)
+            .min_by_key(|&t| {
+                spread(robots.iter().map(|r| r.position_after(t).0))
+                    + spread(robots.iter().map(|r| r.position_after(t).1))
+            })
+            .unwrap();
+        assert_eq!(brute, STEP);
+
+        assert_eq!(part2(&input), STEP);
+        assert!(render(&parse_input(&input), STEP).lines().all(|l| l.len() == WIDTH as usize));
+    }
+}
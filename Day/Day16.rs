@@ -1,3 +1,4 @@
+use std::collections::{BinaryHeap, HashMap, HashSet};
 use std::fs::read_to_string;
 use std::io;
 
@@ -71,6 +72,8 @@ enum Direction {
     West,
 }
 
+type Node = ((i32, i32), Direction);
+
 #[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
 struct State {
     cost: i32,
@@ -143,9 +146,7 @@ fn get_neighbors(
     neighbors
 }
 
-fn find_path(map: &Map, start: Item, end: Item) -> Option<(i32, Vec<(i32, i32)>)> {
-    use std::collections::{BinaryHeap, HashMap};
-
+fn find_path(map: &Map, start: Item, end: Item) -> Option<(i32, HashSet<(i32, i32)>)> {
     let mut heap = BinaryHeap::new();
     let mut costs = HashMap::new();
 
@@ -157,7 +158,11 @@ fn find_path(map: &Map, start: Item, end: Item) -> Option<(i32, Vec<(i32, i32)>)
     heap.push(initial_state);
     costs.insert(((start.x, start.y), Direction::East), 0);
 
-    let mut came_from: HashMap<((i32, i32), Direction), ((i32, i32), Direction)> = HashMap::new();
+    // Every state keeps all predecessors that reach it at its lowest cost, so
+    // walking them back from the end visits every tile of every optimal path.
+    let mut came_from: HashMap<Node, Vec<Node>> = HashMap::new();
+    let mut best_cost = None;
+    let mut end_states = Vec::new();
 
     while let Some(State {
         cost,
@@ -165,22 +170,17 @@ fn find_path(map: &Map, start: Item, end: Item) -> Option<(i32, Vec<(i32, i32)>)
         direction,
     }) = heap.pop()
     {
-        if position == (end.x, end.y) {
-            let mut path = Vec::new();
-            let mut current_state = (position, direction);
-            path.push(current_state.0);
-
-            while let Some(&prev_state) = came_from.get(&current_state) {
-                path.push(prev_state.0);
-                current_state = prev_state;
-
-                if current_state.0 == (start.x, start.y) {
-                    break;
-                }
-            }
+        if costs.get(&(position, direction)).is_some_and(|&c| cost > c) {
+            continue;
+        }
+        if best_cost.is_some_and(|best| cost > best) {
+            break;
+        }
 
-            path.reverse();
-            return Some((cost, path));
+        if position == (end.x, end.y) {
+            best_cost = Some(cost);
+            end_states.push((position, direction));
+            continue;
         }
 
         let is_first_move = position == (start.x, start.y);
@@ -188,22 +188,43 @@ fn find_path(map: &Map, start: Item, end: Item) -> Option<(i32, Vec<(i32, i32)>)
             let move_cost = if direction == next_dir { 1 } else { 1001 };
             let next_cost = cost + move_cost;
 
-            let current_best = costs.get(&(next_pos, next_dir));
-            let is_better = current_best.map_or(true, |&c| next_cost < c);
-
-            if is_better {
-                costs.insert((next_pos, next_dir), next_cost);
-                came_from.insert((next_pos, next_dir), (position, direction));
-                heap.push(State {
-                    cost: next_cost,
-                    position: next_pos,
-                    direction: next_dir,
-                });
+            match costs.get(&(next_pos, next_dir)) {
+                Some(&c) if next_cost > c => {}
+                Some(&c) if next_cost == c => {
+                    came_from
+                        .entry((next_pos, next_dir))
+                        .or_default()
+                        .push((position, direction));
+                }
+                _ => {
+                    costs.insert((next_pos, next_dir), next_cost);
+                    came_from.insert((next_pos, next_dir), vec![(position, direction)]);
+                    heap.push(State {
+                        cost: next_cost,
+                        position: next_pos,
+                        direction: next_dir,
+                    });
+                }
             }
         }
     }
 
-    None
+    let cost = best_cost?;
+    let mut tiles = HashSet::new();
+    let mut seen = HashSet::new();
+    let mut stack = end_states;
+
+    while let Some(current_state) = stack.pop() {
+        if !seen.insert(current_state) {
+            continue;
+        }
+        tiles.insert(current_state.0);
+        if let Some(prev_states) = came_from.get(&current_state) {
+            stack.extend(prev_states.iter().copied());
+        }
+    }
+
+    Some((cost, tiles))
 }
 
 fn main() -> io::Result<()> {
@@ -218,9 +239,9 @@ fn main() -> io::Result<()> {
     println!("Map dimensions: {}x{}", map.len(), map[0].len());
 
     match find_path(&map, robot, end) {
-        Some((cost, path)) => {
+        Some((cost, tiles)) => {
             println!("Shortest path cost: {}", cost);
-            println!("Path: {:?}", path);
+            println!("Tiles on any best path: {}", tiles.len());
         }
         None => {
             println!("No path found!");
@@ -229,3 +250,57 @@ fn main() -> io::Result<()> {
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn solve(input: &str) -> Option<(i32, usize)> {
+        let (map, robot, end) = parse_map(input);
+        find_path(&map, robot, end).map(|(cost, tiles)| (cost, tiles.len()))
+    }
+
+    #[test]
+    fn test_first_example() {
+        let input = "\
+###############
+#.......#....E#
+#.#.###.#.###.#
+#.....#.#...#.#
+#.###.#####.#.#
+#.#.#.......#.#
+#.#.#####.###.#
+#...........#.#
+###.#.#####.#.#
+#...#.....#.#.#
+#.#.#.###.#.#.#
+#.....#...#.#.#
+#.###.#.#.#.#.#
+#S..#.....#...#
+###############";
+        assert_eq!(solve(input), Some((7036, 45)));
+    }
+
+    #[test]
+    fn test_second_example() {
+        let input = "\
+#################
+#...#...#...#..E#
+#.#.#.#.#.#.#.#.#
+#.#.#.#...#...#.#
+#.#.#.#.###.#.#.#
+#...#.#.#.....#.#
+#.#.#.#.#.#####.#
+#.#...#.#.#.....#
+#.#.#####.#.###.#
+#.#.#.......#...#
+#.#.###.#####.###
+#.#.#...#.....#.#
+#.#.#.#####.###.#
+#.#.#.........#.#
+#.#.#.#########.#
+#S#.............#
+#################";
+        assert_eq!(solve(input), Some((11048, 64)));
+    }
+}
@@ -1,35 +1,20 @@
-type Registers = [usize; 3];
-
-#[derive(Debug, PartialEq)]
-enum Instruction {
-    Adv, // 0
-    Bxl, // 1
-    Bst, // 2
-    Jnz, // 3
-    Bxc, // 4
-    Out, // 5
-    Bdv, // 6
-    Cdv, // 7
-}
-
-impl From<u8> for Instruction {
-    fn from(value: u8) -> Self {
-        match value {
-            0 => Self::Adv,
-            1 => Self::Bxl,
-            2 => Self::Bst,
-            3 => Self::Jnz,
-            4 => Self::Bxc,
-            5 => Self::Out,
-            6 => Self::Bdv,
-            7 => Self::Cdv,
-            _ => panic!("Invalid opcode: {}", value),
-        }
-    }
-}
-
-fn parse_input(input: &str) -> (Registers, Vec<u8>) {
-    let mut registers = [0; 3];
+mod day17_vm;
+
+use day17_vm::asm::assemble;
+use day17_vm::compile::Compiled;
+use day17_vm::debugger::Debugger;
+use day17_vm::disasm::{decompile, disassemble, listing};
+use day17_vm::quine::{find_quine, STEP_LIMIT};
+use day17_vm::{run, InvalidOpcode, Registers, Vm, Word};
+use num_bigint::BigUint;
+use std::fmt::Debug;
+use std::time::Instant;
+
+fn parse_input<W: Word>(input: &str) -> (Registers<W>, Vec<u8>)
+where
+    W::Err: Debug,
+{
+    let mut registers = [W::from_u8(0), W::from_u8(0), W::from_u8(0)];
     let mut program = Vec::new();
 
     for line in input.lines().map(|l| l.trim()).filter(|l| !l.is_empty()) {
@@ -49,67 +34,14 @@ fn parse_input(input: &str) -> (Registers, Vec<u8>) {
     (registers, program)
 }
 
-fn combo(registers: &Registers, operand: u8) -> usize {
-    match operand {
-        0..=3 => operand as usize,       // Literal values 0-3
-        4 => registers[0],               // Register A
-        5 => registers[1],               // Register B
-        6 => registers[2],               // Register C
-        _ => panic!("Invalid combo operand: {}", operand),
-    }
-}
-
-fn run(mut registers: Registers, program: &[u8]) -> Vec<u8> {
-    let mut output = Vec::new();
-    let mut ip = 0; // Instruction pointer
-
-    while ip < program.len() {
-        let opcode = program[ip];
-        let operand = program[ip + 1];
-        ip += 2;
-
-        let instruction: Instruction = opcode.into();
-
-        match instruction {
-            Instruction::Adv => {
-                let value = 2_usize.pow(combo(&registers, operand) as u32);
-                registers[0] /= value;
-            }
-            Instruction::Bxl => {
-                registers[1] ^= operand as usize;
-            }
-            Instruction::Bst => {
-                registers[1] = combo(&registers, operand) % 8;
-            }
-            Instruction::Jnz => {
-                if registers[0] != 0 {
-                    ip = operand as usize;
-                }
-            }
-            Instruction::Bxc => {
-                registers[1] ^= registers[2];
-            }
-            Instruction::Out => {
-                let value = combo(&registers, operand) % 8;
-                output.push(value as u8);
-            }
-            Instruction::Bdv => {
-                let value = 2_usize.pow(combo(&registers, operand) as u32);
-                registers[1] = registers[0] / value;
-            }
-            Instruction::Cdv => {
-                let value = 2_usize.pow(combo(&registers, operand) as u32);
-                registers[2] = registers[0] / value;
-            }
-        }
-    }
-
-    output
-}
-
 pub fn solve(input: &str) -> String {
-    let (registers, program) = parse_input(input);
-    let output = run(registers, &program);
+    // Arbitrary precision, so no register value in the input can overflow.
+    let (registers, program) = parse_input::<BigUint>(input);
+    // A bad opcode means the input is wrong, not that the program finished.
+    let mut output = Vec::new();
+    Vm::new(registers, &program)
+        .on_invalid_opcode(InvalidOpcode::Panic)
+        .run(&mut output);
     output
         .into_iter()
         .map(|v| v.to_string())
@@ -117,6 +49,29 @@ pub fn solve(input: &str) -> String {
         .join(",")
 }
 
+/// Times the interpreter against the compiled program on the first `count`
+/// values of A, checking each for printing the program itself.
+fn bench(input: &str, count: usize) {
+    let ([_, b, c], program) = parse_input::<usize>(input);
+    let rate = |elapsed: std::time::Duration| count as f64 / elapsed.as_secs_f64() / 1e6;
+
+    let now = Instant::now();
+    let found = (0..count).filter(|&a| run([a, b, c], &program) == program).count();
+    let elapsed = now.elapsed();
+    println!("interpreter: {} found, {:.1}M A/s ({:.2?})", found, rate(elapsed), elapsed);
+
+    let compiled = Compiled::new(&program);
+    let now = Instant::now();
+    let found = (0..count).filter(|&a| compiled.output([a, b, c]) == program).count();
+    let elapsed = now.elapsed();
+    println!("compiled: {} found, {:.1}M A/s ({:.2?})", found, rate(elapsed), elapsed);
+
+    let now = Instant::now();
+    let found = (0..count).filter(|&a| compiled.prints([a, b, c], &program)).count();
+    let elapsed = now.elapsed();
+    println!("compiled, early exit: {} found, {:.1}M A/s ({:.2?})", found, rate(elapsed), elapsed);
+}
+
 fn main() {
     let input = "\
     Register A: 66752888
@@ -125,8 +80,63 @@ fn main() {
 
     Program:  2,4,1,7,7,5,1,7,0,3,4,1,5,5,3,0
     ";
+    let args: Vec<String> = std::env::args().collect();
+    let flag = |name: &str| args.iter().any(|arg| arg == name);
+
+    if flag("--trace") {
+        tracing_subscriber::fmt().with_max_level(tracing::Level::TRACE).init();
+    }
+    if flag("--debug") {
+        let (registers, program) = parse_input::<BigUint>(input);
+        let mut debugger = Debugger::new(Vm::new(registers, &program));
+        debugger.repl(std::io::stdin().lock(), &mut std::io::stdout()).unwrap();
+        return;
+    }
+    if flag("--source") {
+        // Only the assembler source, so it can be edited and fed to `--assemble`.
+        let (_, program) = parse_input::<usize>(input);
+        match disassemble(&program) {
+            Some(source) => print!("{}", source),
+            None => println!("Cannot disassemble the program"),
+        }
+        return;
+    }
+    if let Some(path) = args.iter().skip_while(|arg| *arg != "--assemble").nth(1) {
+        // Runs a hand-written program on the input's registers instead.
+        let source = std::fs::read_to_string(path).unwrap();
+        let program = match assemble(&source) {
+            Ok(program) => program,
+            Err(err) => return println!("{}: {}", path, err),
+        };
+        let (registers, _) = parse_input::<BigUint>(input);
+        let mut output = Vec::new();
+        let halt = Vm::new(registers, &program).with_step_limit(STEP_LIMIT).run(&mut output);
+        let output = output.iter().map(u8::to_string).collect::<Vec<_>>().join(",");
+        println!("Output: {} ({:?})", output, halt);
+        return;
+    }
+
     let result = solve(input);
     println!("Output: {}", result);
+
+    if flag("--disassemble") {
+        let (_, program) = parse_input::<usize>(input);
+        print!("{}", listing(&program));
+        match decompile(&program) {
+            Ok(code) => print!("{}", code),
+            Err(err) => println!("Cannot decompile: {}", err),
+        }
+    }
+    if flag("--quine") {
+        let ([_, b, c], program) = parse_input::<BigUint>(input);
+        match find_quine(&program, b, c) {
+            Ok(a) => println!("Quine: A = {}", a),
+            Err(err) => println!("No quine: {}", err),
+        }
+    }
+    if flag("--bench") {
+        bench(input, 5_000_000);
+    }
 }
 
 #[cfg(test)]
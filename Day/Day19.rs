@@ -1,46 +1,243 @@
-use std::collections::HashSet;
+use rayon::prelude::*;
 use std::fs;
+use std::time::{Instant, SystemTime};
 
-fn count_possible_designs(towel_patterns: Vec<&str>, designs: Vec<&str>) -> usize {
-    let patterns: HashSet<&str> = towel_patterns.into_iter().collect();
-    let mut possible_count = 0;
+// The towel patterns as a trie over their stripes, so matching from a position
+// in a design only follows patterns that still fit.
+#[derive(Debug, Default)]
+struct Node {
+    children: Vec<(u8, usize)>,
+    is_pattern: bool,
+}
+
+#[derive(Debug)]
+struct Trie {
+    nodes: Vec<Node>,
+}
+
+impl Trie {
+    fn new<'a>(patterns: impl IntoIterator<Item = &'a str>) -> Self {
+        let mut trie = Trie { nodes: vec![Node::default()] };
+
+        for pattern in patterns {
+            let mut node = 0;
+            for &stripe in pattern.as_bytes() {
+                node = match trie.child(node, stripe) {
+                    Some(child) => child,
+                    None => {
+                        trie.nodes.push(Node::default());
+                        let child = trie.nodes.len() - 1;
+                        trie.nodes[node].children.push((stripe, child));
+                        child
+                    }
+                };
+            }
+            trie.nodes[node].is_pattern = true;
+        }
+
+        trie
+    }
+
+    fn child(&self, node: usize, stripe: u8) -> Option<usize> {
+        self.nodes[node]
+            .children
+            .iter()
+            .find(|&&(edge, _)| edge == stripe)
+            .map(|&(_, child)| child)
+    }
+
+    // The lengths of the patterns that `design` starts with, shortest first.
+    fn prefixes<'a>(&'a self, design: &'a [u8]) -> impl Iterator<Item = usize> + 'a {
+        design
+            .iter()
+            .scan(0, |node, &stripe| {
+                *node = self.child(*node, stripe)?;
+                Some(*node)
+            })
+            .enumerate()
+            .filter(|&(_, node)| self.nodes[node].is_pattern)
+            .map(|(idx, _)| idx + 1)
+    }
+}
+
+// The number of ways to lay patterns end to end into `design`. ways[i] counts
+// the ways to make the first i stripes, and only positions some arrangement
+// reaches are matched against the trie.
+fn count_arrangements(trie: &Trie, design: &str) -> u64 {
+    let design = design.as_bytes();
+    let mut ways = vec![0u64; design.len() + 1];
+    ways[0] = 1;
+
+    for start in 0..design.len() {
+        if ways[start] == 0 {
+            continue;
+        }
+        for len in trie.prefixes(&design[start..]) {
+            ways[start + len] += ways[start];
+        }
+    }
+
+    ways[design.len()]
+}
 
-    for design in designs {
-        let design_len = design.len();
-        let mut dp = vec![false; design_len + 1];
-        dp[0] = true; // Base case: empty design is always possible.
+// completions[i] is the number of ways to finish `design` from stripe i, so a
+// position with none is a dead end.
+fn completions(trie: &Trie, design: &str) -> Vec<u64> {
+    let design = design.as_bytes();
+    let mut completions = vec![0u64; design.len() + 1];
+    completions[design.len()] = 1;
 
-        for i in 1..=design_len {
-            for j in 0..i {
-                if dp[j] && patterns.contains(&design[j..i]) {
-                    dp[i] = true;
+    for start in (0..design.len()).rev() {
+        completions[start] = trie
+            .prefixes(&design[start..])
+            .map(|len| completions[start + len])
+            .sum();
+    }
+
+    completions
+}
+
+// The arrangements of one design, listed lazily in lexicographic order. Two
+// patterns that fit at the same position are prefixes of each other, so taking
+// the shorter one first keeps the order, and the completion counts make sure
+// no branch is tried that can't finish.
+struct Arrangements<'a> {
+    trie: &'a Trie,
+    design: &'a str,
+    completions: Vec<u64>,
+    // Where each pattern of the current arrangement ends.
+    cuts: Vec<usize>,
+    started: bool,
+}
+
+impl<'a> Arrangements<'a> {
+    fn new(trie: &'a Trie, design: &'a str) -> Self {
+        Arrangements {
+            trie,
+            design,
+            completions: completions(trie, design),
+            cuts: Vec::new(),
+            started: false,
+        }
+    }
+
+    fn total(&self) -> u64 {
+        self.completions[0]
+    }
+
+    // The end of the shortest pattern from `start` that ends past `after` and
+    // can still be finished.
+    fn next_cut(&self, start: usize, after: usize) -> Option<usize> {
+        self.trie
+            .prefixes(&self.design.as_bytes()[start..])
+            .map(|len| start + len)
+            .find(|&end| end > after && self.completions[end] > 0)
+    }
+
+    // Completes the current arrangement with the smallest patterns.
+    fn fill(&mut self) {
+        let mut start = self.cuts.last().copied().unwrap_or(0);
+        while start < self.design.len() {
+            start = self.next_cut(start, start).unwrap();
+            self.cuts.push(start);
+        }
+    }
+
+    fn pieces(&self, cuts: &[usize]) -> Vec<&'a str> {
+        let design = self.design;
+        let starts = std::iter::once(0).chain(cuts.iter().copied());
+        starts.zip(cuts).map(|(start, &end)| &design[start..end]).collect()
+    }
+
+    // One arrangement, each with the same chance. `random(n)` must return a
+    // uniformly random number below `n`.
+    fn sample(&self, mut random: impl FnMut(u64) -> u64) -> Option<Vec<&'a str>> {
+        if self.total() == 0 {
+            return None;
+        }
+
+        let mut cuts = Vec::new();
+        let mut start = 0;
+        while start < self.design.len() {
+            // Each pattern gets picked in proportion to the ways to finish
+            // after it.
+            let mut pick = random(self.completions[start]);
+            for len in self.trie.prefixes(&self.design.as_bytes()[start..]) {
+                let ways = self.completions[start + len];
+                if pick < ways {
+                    start += len;
                     break;
                 }
+                pick -= ways;
             }
+            cuts.push(start);
         }
 
-        if dp[design_len] {
-            possible_count += 1;
+        Some(self.pieces(&cuts))
+    }
+}
+
+impl<'a> Iterator for Arrangements<'a> {
+    type Item = Vec<&'a str>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if !self.started {
+            self.started = true;
+            if self.total() == 0 {
+                return None;
+            }
+        } else {
+            // Move the last cut that can move to its next option.
+            loop {
+                let end = self.cuts.pop()?;
+                let start = self.cuts.last().copied().unwrap_or(0);
+                if let Some(next) = self.next_cut(start, end) {
+                    self.cuts.push(next);
+                    break;
+                }
+            }
         }
+
+        self.fill();
+        Some(self.pieces(&self.cuts))
     }
+}
 
-    possible_count
+// A number below `n` from splitmix64, close enough to uniform for sampling.
+fn random_below(state: &mut u64, n: u64) -> u64 {
+    *state = state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+    let mut z = *state;
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    (((z ^ (z >> 31)) as u128 * n as u128) >> 64) as u64
+}
+
+// How many designs can be made at all, and the total number of arrangements
+// over all of them.
+fn solve(patterns: &[&str], designs: &[&str]) -> (usize, u64) {
+    let trie = Trie::new(patterns.iter().copied());
+
+    designs
+        .par_iter()
+        .map(|design| count_arrangements(&trie, design))
+        .fold(|| (0, 0), |(possible, total), ways| (possible + (ways > 0) as usize, total + ways))
+        .reduce(|| (0, 0), |a, b| (a.0 + b.0, a.1 + b.1))
 }
 
 fn main() -> std::io::Result<()> {
     // Load the input from the file
     let input = fs::read_to_string("Day7.txt")?;
-    
+
     // Split the input into sections
     let mut sections = input.split("\n\n");
-    
+
     // Parse towel patterns
     let towel_patterns: Vec<&str> = sections
         .next()
         .expect("No towel patterns found")
         .split(", ")
         .collect();
-    
+
     // Parse designs
     let designs: Vec<&str> = sections
         .next()
@@ -48,9 +245,105 @@ fn main() -> std::io::Result<()> {
         .lines()
         .collect();
 
-    // Calculate the number of possible designs
-    let result = count_possible_designs(towel_patterns, designs);
-    println!("Number of possible designs: {}", result);
+    // Both parts come from the same counts
+    let now = Instant::now();
+    let (part01, part02) = solve(&towel_patterns, &designs);
+
+    println!("part01: {}", part01);
+    println!("part02: {} ({:.2?})", part02, now.elapsed());
+
+    let trie = Trie::new(towel_patterns.iter().copied());
+
+    if let Some(count) = std::env::args().skip_while(|arg| arg != "--list").nth(1) {
+        let count = count.parse::<usize>().expect("--list takes a number");
+        for design in &designs {
+            println!("{}:", design);
+            for arrangement in Arrangements::new(&trie, design).take(count) {
+                println!("  {}", arrangement.join(", "));
+            }
+        }
+    }
+
+    if std::env::args().any(|arg| arg == "--sample") {
+        let mut state = SystemTime::now()
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .unwrap()
+            .as_nanos() as u64;
+        for design in &designs {
+            match Arrangements::new(&trie, design).sample(|n| random_below(&mut state, n)) {
+                Some(arrangement) => println!("{}: {}", design, arrangement.join(", ")),
+                None => println!("{}: impossible", design),
+            }
+        }
+    }
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::*;
+
+    const PATTERNS: [&str; 8] = ["r", "wr", "b", "g", "bwu", "rb", "gb", "br"];
+
+    #[test]
+    fn test_example() {
+        let designs = ["brwrr", "bggr", "gbbr", "rrbgbr", "ubwu", "bwurrg", "brgr", "bbrwb"];
+        assert_eq!(solve(&PATTERNS, &designs), (6, 16));
+
+        let trie = Trie::new(PATTERNS);
+        let counts = designs.map(|design| count_arrangements(&trie, design));
+        assert_eq!(counts, [2, 1, 4, 6, 0, 1, 2, 0]);
+    }
+
+    #[test]
+    fn test_arrangements() {
+        let trie = Trie::new(PATTERNS);
+        let listed: Vec<_> = Arrangements::new(&trie, "rrbgbr").collect();
+        assert_eq!(
+            listed,
+            [
+                vec!["r", "r", "b", "g", "b", "r"],
+                vec!["r", "r", "b", "g", "br"],
+                vec!["r", "r", "b", "gb", "r"],
+                vec!["r", "rb", "g", "b", "r"],
+                vec!["r", "rb", "g", "br"],
+                vec!["r", "rb", "gb", "r"],
+            ]
+        );
+
+        for design in ["brwrr", "bggr", "gbbr", "ubwu", "bwurrg", "brgr", "bbrwb", "", "rbrbrbrbgbgbrwrbwu"] {
+            let listed: Vec<_> = Arrangements::new(&trie, design).collect();
+            assert_eq!(listed.len() as u64, count_arrangements(&trie, design), "{}", design);
+            assert!(listed.windows(2).all(|pair| pair[0] < pair[1]), "{}", design);
+            assert!(listed.iter().all(|pieces| pieces.concat() == design));
+        }
+        assert_eq!(Arrangements::new(&trie, "").next(), Some(vec![]));
+    }
+
+    #[test]
+    fn test_sample() {
+        let trie = Trie::new(PATTERNS);
+        let mut state = 19;
+        let arrangements = Arrangements::new(&trie, "rrbgbr");
+        let listed: Vec<_> = Arrangements::new(&trie, "rrbgbr").collect();
+
+        let mut seen = vec![0; listed.len()];
+        for _ in 0..6000 {
+            let sample = arrangements.sample(|n| random_below(&mut state, n)).unwrap();
+            seen[listed.iter().position(|pieces| *pieces == sample).unwrap()] += 1;
+        }
+        assert!(seen.iter().all(|&count| (850..1150).contains(&count)), "{:?}", seen);
+
+        assert_eq!(Arrangements::new(&trie, "ubwu").sample(|n| random_below(&mut state, n)), None);
+    }
+
+    #[test]
+    fn test_prefixes() {
+        let trie = Trie::new(["b", "bw", "bwu", "wu", "bwuu"]);
+        assert_eq!(trie.prefixes(b"bwur").collect::<Vec<_>>(), [1, 2, 3]);
+        assert_eq!(trie.prefixes(b"wbu").count(), 0);
+        assert_eq!(count_arrangements(&trie, "bwuwu"), 2);
+        assert_eq!(count_arrangements(&trie, ""), 1);
+    }
+}
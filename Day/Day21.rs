@@ -1,67 +1,106 @@
 use std::{
     collections::{HashMap, HashSet, VecDeque},
-    sync::OnceLock,
+    fmt::Debug,
+    iter::Sum,
+    ops::{Add, Mul},
+    str::FromStr,
 };
 
-use cached::proc_macro::cached;
 use itertools::Itertools;
+use num_bigint::BigUint;
 
-// The next two functions basically find all the possible shortest paths between
-// any two points on each of the keypads. Because the combinations are small, we
-// can pre-compute.
-//
-// We use OnceLock here and in the next function (previously would be
-// lazy_static) so we can use the cached macro.  There may be an easier way to
-// do this but it is nice that the cached macro has a reduced parameter list.
-fn numeric_paths() -> &'static HashMap<(char, char), Vec<String>> {
-    static NUMERIC_PATHS: OnceLock<HashMap<(char, char), Vec<String>>> = OnceLock::new();
-    NUMERIC_PATHS.get_or_init(|| {
-        // The first value if the button, the second is a list of its neighbors
-        // and the direction you'd need to go to get to it.
-        let numeric_keypad = vec![
-            ('7', vec![('4', 'v'), ('8', '>')]),
-            ('8', vec![('5', 'v'), ('9', '>'), ('7', '<')]),
-            ('9', vec![('6', 'v'), ('8', '<')]),
-            ('4', vec![('1', 'v'), ('5', '>'), ('7', '^')]),
-            ('5', vec![('2', 'v'), ('6', '>'), ('4', '<'), ('8', '^')]),
-            ('6', vec![('3', 'v'), ('5', '<'), ('9', '^')]),
-            ('1', vec![('2', '>'), ('4', '^')]),
-            ('2', vec![('3', '>'), ('5', '^'), ('1', '<'), ('0', 'v')]),
-            ('0', vec![('2', '^'), ('A', '>')]),
-            ('3', vec![('6', '^'), ('2', '<'), ('A', 'v')]),
-            ('A', vec![('0', '<'), ('3', '^')]),
-        ]
-        .into_iter()
-        .collect::<HashMap<char, Vec<(char, char)>>>();
+// Keypads are drawn one row per line, one character per button, with a space
+// for the gap the robot arms must never point at.
+const NUMERIC: &str = "789\n456\n123\n 0A";
+const DIRECTIONAL: &str = " ^A\n<v>";
 
-        // For each combination of buttons, find the shortest paths between them.
-        numeric_keypad
-            .keys()
-            .cartesian_product(numeric_keypad.keys())
-            .map(|(&a, &b)| ((a, b), find_shortest_paths(&numeric_keypad, a, b)))
-            .collect()
-    })
+// How far one press of a direction moves an arm, as (row, column).
+fn offset(dir: char) -> Option<(i32, i32)> {
+    match dir {
+        '^' => Some((-1, 0)),
+        'v' => Some((1, 0)),
+        '<' => Some((0, -1)),
+        '>' => Some((0, 1)),
+        _ => None,
+    }
 }
 
-fn direction_paths() -> &'static HashMap<(char, char), Vec<String>> {
-    static DIRECTION_PATHS: OnceLock<HashMap<(char, char), Vec<String>>> = OnceLock::new();
-    DIRECTION_PATHS.get_or_init(|| {
-        let direction_keypad = vec![
-            ('^', vec![('A', '>'), ('v', 'v')]),
-            ('A', vec![('^', '<'), ('>', 'v')]),
-            ('>', vec![('A', '^'), ('v', '<')]),
-            ('<', vec![('v', '>')]),
-            ('v', vec![('<', '<'), ('^', '^'), ('>', '>')]),
-        ]
-        .into_iter()
-        .collect::<HashMap<char, Vec<(char, char)>>>();
+// A keypad: where each button is, and all the possible shortest paths between
+// any two of its buttons. Because the keypads are small, we can pre-compute
+// them.
+struct Keypad {
+    buttons: HashMap<(i32, i32), char>,
+    positions: HashMap<char, (i32, i32)>,
+    paths: HashMap<(char, char), Vec<String>>,
+}
+
+impl Keypad {
+    fn parse(layout: &str) -> Keypad {
+        let buttons = layout
+            .lines()
+            .enumerate()
+            .flat_map(|(row, line)| {
+                line.chars()
+                    .enumerate()
+                    .filter(|(_, button)| *button != ' ')
+                    .map(move |(col, button)| ((row as i32, col as i32), button))
+            })
+            .collect::<HashMap<(i32, i32), char>>();
+        assert_eq!(
+            buttons.values().collect::<HashSet<_>>().len(),
+            buttons.len(),
+            "duplicate button in keypad {:?}",
+            layout
+        );
 
-        direction_keypad
+        // Each button and a list of its neighbors, with the direction you'd
+        // need to go to get to it. The gap has no button, so it is never a
+        // neighbor.
+        let neighbors = buttons
+            .iter()
+            .map(|(&(row, col), &button)| {
+                let next = "^v<>"
+                    .chars()
+                    .filter_map(|dir| {
+                        let (dr, dc) = offset(dir)?;
+                        buttons.get(&(row + dr, col + dc)).map(|&next| (next, dir))
+                    })
+                    .collect();
+                (button, next)
+            })
+            .collect::<HashMap<char, Vec<(char, char)>>>();
+
+        // For each combination of buttons, find the shortest paths between them.
+        let paths = neighbors
             .keys()
-            .cartesian_product(direction_keypad.keys())
-            .map(|(&a, &b)| ((a, b), find_shortest_paths(&direction_keypad, a, b)))
-            .collect()
-    })
+            .cartesian_product(neighbors.keys())
+            .map(|(&a, &b)| ((a, b), find_shortest_paths(&neighbors, a, b)))
+            .collect();
+        let positions = buttons.iter().map(|(&position, &button)| (button, position)).collect();
+        Keypad {
+            buttons,
+            positions,
+            paths,
+        }
+    }
+
+    // Where the arm ends up after one press of `dir`, or `None` if that would
+    // leave the keypad or point it at the gap.
+    fn step(&self, arm: char, dir: char) -> Option<char> {
+        let (row, col) = self.positions[&arm];
+        let (dr, dc) = offset(dir)?;
+        self.buttons.get(&(row + dr, col + dc)).copied()
+    }
+}
+
+// Why a sequence of presses could not be typed.
+#[derive(Debug, PartialEq, Eq)]
+enum SimulationError {
+    // The press at this index moved the arm of a robot off its keypad or over
+    // the gap. Layer 0 is the robot the human controls.
+    Gap { press: usize, layer: usize },
+    // The human pressed something that is not a directional button.
+    UnknownButton { press: usize, button: char },
 }
 
 // Find all the shortest paths between two points on a keypad. This is a bfs
@@ -109,64 +148,519 @@ fn find_shortest_paths(
     paths
 }
 
+// What the costs are counted in: `usize` is plenty for the puzzle, but each
+// robot multiplies the length by about 2.5, so deeper chains need u128 or
+// BigUint.
+trait Count: Clone + Ord + From<u8> + Add<Output = Self> + Sum {}
+
+impl<C: Clone + Ord + From<u8> + Add<Output = C> + Sum> Count for C {}
+
+// A code typed on the `numeric` keypad by a robot, which is driven through a
+// chain of robots on `directional` keypads.
+struct Keypads<C = usize> {
+    numeric: Keypad,
+    directional: Keypad,
+    // What one press of a button costs, by who presses it: layer 0 is the
+    // human, layer 1 the robot they drive, and so on down to the robot on the
+    // numeric keypad.
+    cost: Box<dyn Fn(usize, char) -> C>,
+    // For each depth, the cost of a robot on a directional keypad moving its
+    // arm from one button to another and pressing it. Each depth only needs
+    // the one before, so they are built bottom up instead of recursing, and
+    // deep chains cannot overflow the stack.
+    moves: Vec<HashMap<(char, char), C>>,
+}
+
+impl<C: Count> Keypads<C> {
+    // By default only the human's presses count, one each, so the cost of a
+    // sequence is its length.
+    fn new(numeric: Keypad, directional: Keypad) -> Self {
+        Keypads {
+            numeric,
+            directional,
+            cost: Box::new(|layer, _| C::from(u8::from(layer == 0))),
+            moves: Vec::new(),
+        }
+    }
+
+    fn with_costs(mut self, cost: impl Fn(usize, char) -> C + 'static) -> Self {
+        self.cost = Box::new(cost);
+        self.moves.clear();
+        self
+    }
+
+    // The cost of the layer below typing `path` (which ends in 'A'), when
+    // `depth` robots are left between it and the human.
+    fn path_cost(&self, path: &str, depth: usize) -> C {
+        match depth {
+            // If we've reached the end, the human presses the path themselves.
+            0 => path.chars().map(|button| (self.cost)(0, button)).sum(),
+            _ => self.sequence_cost(path, depth - 1, false),
+        }
+    }
+
+    // The cost of moving from `a` to `b` and pressing it. The robot typing
+    // pays for pressing the button, and we need to find the cheapest path
+    // among all the paths for the layer below to move its arm there.
+    fn move_cost(&self, a: char, b: char, depth: usize, numeric: bool) -> C {
+        // Pick the right keypad paths.
+        let paths = if numeric { &self.numeric.paths } else { &self.directional.paths };
+        let shortest_paths = paths
+            .get(&(a, b))
+            .unwrap_or_else(|| panic!("no button {:?} or {:?} on the keypad", a, b));
+        let press = (self.cost)(depth + 1, b);
+        press
+            + shortest_paths
+                .iter()
+                // We put and 'A' at the end because they'll need to
+                // hit the 'A' button to tell the next in line to push their button.
+                .map(|path| self.path_cost(&(path.clone() + "A"), depth))
+                .min()
+                .unwrap()
+    }
+
+    // Makes sure the move costs are known for every depth up to `depth`.
+    fn extend_moves(&mut self, depth: usize) {
+        while self.moves.len() <= depth {
+            let depth = self.moves.len();
+            let moves = self
+                .directional
+                .paths
+                .keys()
+                .map(|&(a, b)| ((a, b), self.move_cost(a, b, depth, false)))
+                .collect();
+            self.moves.push(moves);
+        }
+    }
+
+    // Like `find_shortest_sequence`, once `extend_moves` has covered `depth`.
+    fn sequence_cost(&self, sequence: &str, depth: usize, numeric: bool) -> C {
+        // We want to find the path from each button to the next. All robots start
+        // at 'A', so we prefix the windows with that.
+        ("A".to_string() + sequence)
+            .chars()
+            .tuple_windows()
+            .map(|(a, b)| match numeric {
+                true => self.move_cost(a, b, depth, true),
+                false => self.moves[depth][&(a, b)].clone(),
+            })
+            .sum()
+    }
+
+    // The cheapest way to type `sequence`, counting the presses of every layer
+    // from the human down to the robot typing it. Only the shortest paths
+    // between buttons are tried, which is enough for the puzzle's costs.
+    fn find_shortest_sequence(&mut self, sequence: &str, depth: usize, numeric: bool) -> C {
+        self.extend_moves(depth);
+        self.sequence_cost(sequence, depth, numeric)
+    }
+
+    // One shortest sequence of presses for the human at the top of the chain,
+    // produced lazily: at 25 robots it is far too long to hold in memory.
+    fn presses(&mut self, code: &str, depth: usize) -> Presses<'_, C> {
+        self.extend_moves(depth);
+        Presses {
+            keypads: self,
+            stack: vec![Layer::new(code, depth, true)],
+            pending: VecDeque::new(),
+        }
+    }
+
+    // Feeds one press of the human's directional keypad down the chain of
+    // `arms`, the last of which is on the numeric keypad. Returns the button
+    // typed on the numeric keypad, if any, or the layer whose arm hit the gap.
+    fn press(&self, arms: &mut [char], mut button: char) -> Result<Option<char>, usize> {
+        let last = arms.len() - 1;
+        for (layer, arm) in arms.iter_mut().enumerate() {
+            let keypad = if layer == last { &self.numeric } else { &self.directional };
+            if button != 'A' {
+                *arm = keypad.step(*arm, button).ok_or(layer)?;
+                return Ok(None);
+            }
+            // Pressing 'A' makes this robot press the button under its arm.
+            button = *arm;
+        }
+        Ok(Some(button))
+    }
+
+    // What the presses type on the numeric keypad through `depth` robots on
+    // directional keypads, every arm starting at 'A'.
+    fn simulate(&self, presses: &str, depth: usize) -> Result<String, SimulationError> {
+        let mut arms = vec!['A'; depth + 1];
+        let mut typed = String::new();
+        for (press, button) in presses.chars().enumerate() {
+            if button != 'A' && offset(button).is_none() {
+                return Err(SimulationError::UnknownButton { press, button });
+            }
+            match self.press(&mut arms, button) {
+                Ok(Some(typed_button)) => typed.push(typed_button),
+                Ok(None) => {}
+                Err(layer) => return Err(SimulationError::Gap { press, layer }),
+            }
+        }
+        Ok(typed)
+    }
+
+    // The cost of the shortest sequence multiplied by the number at the
+    // beginning of the code.
+    fn complexity(&mut self, code: &str, depth: usize) -> C
+    where
+        C: FromStr + Mul<Output = C>,
+        C::Err: Debug,
+    {
+        self.find_shortest_sequence(code, depth, true) * code.trim_end_matches('A').parse::<C>().unwrap()
+    }
+}
+
+// The buttons one keypad in the chain still has to press, and where its arm
+// is pointing. Every robot starts at 'A'.
+struct Layer {
+    buttons: Vec<char>,
+    next: usize,
+    arm: char,
+    depth: usize,
+    numeric: bool,
+}
+
+impl Layer {
+    fn new(buttons: &str, depth: usize, numeric: bool) -> Self {
+        Layer {
+            buttons: buttons.chars().collect(),
+            next: 0,
+            arm: 'A',
+            depth,
+            numeric,
+        }
+    }
+}
+
+// Expands one layer at a time, keeping only the current move of each robot,
+// so the memory used grows with the depth and not the length.
+struct Presses<'a, C> {
+    keypads: &'a mut Keypads<C>,
+    stack: Vec<Layer>,
+    pending: VecDeque<char>,
+}
+
+impl<C: Count> Iterator for Presses<'_, C> {
+    type Item = char;
+
+    fn next(&mut self) -> Option<char> {
+        while self.pending.is_empty() {
+            let layer = self.stack.last_mut()?;
+            let Some(&button) = layer.buttons.get(layer.next) else {
+                self.stack.pop();
+                continue;
+            };
+            layer.next += 1;
+            let (from, depth, numeric) = (layer.arm, layer.depth, layer.numeric);
+            layer.arm = button;
+
+            let paths = if numeric { &self.keypads.numeric.paths } else { &self.keypads.directional.paths };
+            let path = paths[&(from, button)]
+                .clone()
+                .into_iter()
+                .map(|path| path + "A")
+                .min_by_key(|path| self.keypads.path_cost(path, depth))
+                .unwrap();
+            match depth {
+                // The human presses the buttons for this move directly.
+                0 => self.pending.extend(path.chars()),
+                // Otherwise the robot below has to type the cheapest of the
+                // paths, so expand that one next.
+                _ => self.stack.push(Layer::new(&path, depth - 1, false)),
+            }
+        }
+        self.pending.pop_front()
+    }
+}
+
 fn main() {
     let input = include_str!("../Day7.txt");
+    let mut keypads: Keypads = Keypads::new(Keypad::parse(NUMERIC), Keypad::parse(DIRECTIONAL));
 
     // We want to find the shortest sequence and then multiply it by the number
     // at the beginning of the line.
     let now = std::time::Instant::now();
-    let p1 = input
-        .lines()
-        .map(|line| {
-            find_shortest_sequence(line.to_string(), 2, true)
-                * line.trim_end_matches('A').parse::<usize>().unwrap()
-        })
-        .sum::<usize>();
+    let p1 = input.lines().map(|line| keypads.complexity(line, 2)).sum::<usize>();
     println!("p1: {} ({:?})", p1, now.elapsed());
 
     let now = std::time::Instant::now();
-    let p2 = input
-        .lines()
-        .map(|line| {
-            find_shortest_sequence(line.to_string(), 25, true)
-                * line.trim_end_matches('A').parse::<usize>().unwrap()
-        })
-        .sum::<usize>();
+    let p2 = input.lines().map(|line| keypads.complexity(line, 25)).sum::<usize>();
     println!("p2: {} ({:?})", p2, now.elapsed());
+
+    if std::env::args().any(|arg| arg == "--presses") {
+        for line in input.lines() {
+            let presses = keypads.presses(line, 2).collect::<String>();
+            assert_eq!(keypads.simulate(&presses, 2).as_deref(), Ok(line));
+            println!("{}: {}", line, presses);
+        }
+    }
+
+    // How long the chain can get is only a matter of time and memory.
+    if let Some(depth) = std::env::args().skip_while(|arg| arg != "--depth").nth(1) {
+        let depth = depth.parse::<usize>().unwrap();
+        let mut keypads: Keypads<BigUint> = Keypads::new(Keypad::parse(NUMERIC), Keypad::parse(DIRECTIONAL));
+        let now = std::time::Instant::now();
+        let total = input.lines().map(|line| keypads.complexity(line, depth)).sum::<BigUint>();
+        println!("depth {}: {} ({:?})", depth, total, now.elapsed());
+    }
+
+    // What one press costs on each layer, the human's first. Layers past the
+    // list press for free.
+    if let Some(weights) = std::env::args().skip_while(|arg| arg != "--weights").nth(1) {
+        let weights = weights.split(',').map(|weight| weight.parse::<usize>().unwrap()).collect::<Vec<_>>();
+        let mut keypads: Keypads = Keypads::new(Keypad::parse(NUMERIC), Keypad::parse(DIRECTIONAL))
+            .with_costs(move |layer, _| weights.get(layer).copied().unwrap_or(0));
+        for line in input.lines() {
+            let cost = keypads.find_shortest_sequence(line, 2, true);
+            let presses = keypads.presses(line, 2).collect::<String>();
+            println!("{}: costs {} with {}", line, cost, presses);
+        }
+    }
 }
 
-#[cached]
-fn find_shortest_sequence(sequence: String, depth: usize, numeric: bool) -> usize {
-    // Pick the right keypad paths.
-    let paths = if numeric {
-        numeric_paths()
-    } else {
-        direction_paths()
-    };
-
-    // We want to find the path from each button to the next. All robots start
-    // at 'A', so we prefix the windows with that.
-    ("A".to_string() + &sequence)
-        .chars()
-        .tuple_windows()
-        .map(|(a, b)| {
-            let shortest_paths = paths.get(&(a, b)).unwrap();
-            match depth {
-                // If we've reached the end, we just use the shortest path length.
-                0 => shortest_paths[0].len() + 1,
-                // Otherwise, we need to find the smallest path among all the paths.
-                _ => shortest_paths
-                    .iter()
-                    .cloned()
-                    .map(|mut path| {
-                        // We put and 'A' at the end because they'll need to
-                        // hit the 'A' button to tell the next in line to push their button.
-                        path.push('A');
-                        find_shortest_sequence(path, depth - 1, false)
-                    })
-                    .min()
-                    .unwrap(),
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cmp::Reverse;
+    use std::collections::BinaryHeap;
+
+    const CODES: [&str; 5] = ["029A", "980A", "179A", "456A", "379A"];
+
+    fn keypads() -> Keypads {
+        Keypads::new(Keypad::parse(NUMERIC), Keypad::parse(DIRECTIONAL))
+    }
+
+    #[test]
+    fn test_example() {
+        let mut keypads = keypads();
+        assert_eq!(keypads.find_shortest_sequence("029A", 2, true), 68);
+        assert_eq!(CODES.iter().map(|code| keypads.complexity(code, 2)).sum::<usize>(), 126384);
+    }
+
+    #[test]
+    fn test_presses_are_shortest() {
+        let mut keypads = keypads();
+        assert_eq!(keypads.presses("029A", 0).collect::<String>().len(), 12);
+        for depth in 0..6 {
+            for code in CODES {
+                let length = keypads.find_shortest_sequence(code, depth, true);
+                assert_eq!(keypads.presses(code, depth).count(), length, "{} at depth {}", code, depth);
+            }
+        }
+    }
+
+    #[test]
+    fn test_presses_stream_at_full_depth() {
+        let mut keypads = keypads();
+        let start = keypads.presses("029A", 25).take(10_000).collect::<String>();
+        assert_eq!(start.len(), 10_000);
+        assert!(start.chars().all(|press| "<>^vA".contains(press)));
+    }
+
+    #[test]
+    fn test_simulate() {
+        let keypads = keypads();
+        let presses = "<vA<AA>>^AvAA<^A>A<v<A>>^AvA^A<vA>^A<v<A>^A>AAvA^A<v<A>A>^AAAvA<^A>A";
+        assert_eq!(keypads.simulate(presses, 2), Ok("029A".to_string()));
+        assert_eq!(keypads.simulate("<A^A>^^AvvvA", 0), Ok("029A".to_string()));
+
+        // From 'A', two steps left on the numeric keypad is the gap.
+        assert_eq!(keypads.simulate("<<A", 0), Err(SimulationError::Gap { press: 1, layer: 0 }));
+        // Up from 'A' on the first robot's keypad is off the edge.
+        assert_eq!(keypads.simulate("<A^A", 1), Err(SimulationError::Gap { press: 2, layer: 0 }));
+        // The first robot points at '<' and presses it twice, moving the arm on
+        // the numeric keypad from 'A' to '0' and then into the gap.
+        assert_eq!(keypads.simulate("v<<AA", 1), Err(SimulationError::Gap { press: 4, layer: 1 }));
+        assert_eq!(keypads.simulate("<x", 0), Err(SimulationError::UnknownButton { press: 1, button: 'x' }));
+    }
+
+    // What one press of the human costs across the whole chain: each 'A' makes
+    // the next robot press the button under its arm, until one of them presses
+    // a direction.
+    fn press_cost(keypads: &Keypads, arms: &[char], button: char) -> usize {
+        let mut cost = (keypads.cost)(0, button);
+        if button == 'A' {
+            for (layer, &arm) in arms.iter().enumerate() {
+                cost += (keypads.cost)(layer + 1, arm);
+                if arm != 'A' {
+                    break;
+                }
+            }
+        }
+        cost
+    }
+
+    // The true cheapest way to type the code, by Dijkstra's algorithm over every
+    // arm position and how much of the code has been typed. Unlike the solver
+    // it tries every path, not just the shortest ones.
+    fn brute_force(keypads: &Keypads, code: &str, depth: usize) -> usize {
+        let code = code.chars().collect_vec();
+        let mut best = HashMap::from([((vec!['A'; depth + 1], 0), 0)]);
+        let mut heap = BinaryHeap::from([Reverse((0, vec!['A'; depth + 1], 0))]);
+
+        while let Some(Reverse((cost, arms, typed))) = heap.pop() {
+            if typed == code.len() {
+                return cost;
+            }
+            if best[&(arms.clone(), typed)] < cost {
+                continue;
+            }
+            for button in "<>^vA".chars() {
+                let cost = cost + press_cost(keypads, &arms, button);
+                let mut arms = arms.clone();
+                let typed = match keypads.press(&mut arms, button) {
+                    Ok(None) => typed,
+                    Ok(Some(button)) if button == code[typed] => typed + 1,
+                    _ => continue,
+                };
+                if best.get(&(arms.clone(), typed)).is_none_or(|&known| cost < known) {
+                    best.insert((arms.clone(), typed), cost);
+                    heap.push(Reverse((cost, arms, typed)));
+                }
+            }
+        }
+        unreachable!("{:?} cannot be typed", code)
+    }
+
+    #[test]
+    fn test_solver_sequences_are_valid_and_optimal() {
+        let mut keypads = keypads();
+        // The example codes, plus codes that cross the gap's row and column
+        // and repeat buttons.
+        let others = ["000A", "741A", "147A", "704A", "407A", "310A", "963A", "852A", "159A", "999A"];
+
+        for code in CODES.into_iter().chain(others) {
+            for depth in 0..=3 {
+                let presses = keypads.presses(code, depth).collect::<String>();
+                assert_eq!(keypads.simulate(&presses, depth).as_ref(), Ok(&code.to_string()), "{} at depth {}", code, depth);
+                if depth <= 2 {
+                    assert_eq!(presses.len(), brute_force(&keypads, code, depth), "{} at depth {}", code, depth);
+                }
             }
-        })
-        .sum::<usize>()
-}
\ No newline at end of file
+        }
+    }
+
+    #[test]
+    fn test_weighted_costs() {
+        let mut usual = keypads();
+        let slow_human = |layer: usize, _: char| if layer == 0 { 3 } else { 1 };
+        let expensive_a = |layer: usize, button: char| match (layer, button) {
+            (0, 'A') => 4,
+            (0, _) => 1,
+            _ => 0,
+        };
+        let mut slow = keypads().with_costs(slow_human);
+        let mut expensive = keypads().with_costs(expensive_a);
+
+        for code in CODES {
+            // Every layer pays one per press, so the presses below the human
+            // add at least one for each button the robots type.
+            let length = usual.find_shortest_sequence(code, 2, true);
+            assert!(slow.find_shortest_sequence(code, 2, true) > 3 * length);
+
+            for depth in 0..=2 {
+                for keypads in [&mut slow, &mut expensive] {
+                    let cost = keypads.find_shortest_sequence(code, depth, true);
+                    assert_eq!(cost, brute_force(keypads, code, depth), "{} at depth {}", code, depth);
+
+                    // The streamed presses are the cheapest ones, not the
+                    // shortest ones.
+                    let presses = keypads.presses(code, depth).collect::<String>();
+                    assert_eq!(keypads.simulate(&presses, depth).as_deref(), Ok(code));
+                    let mut arms = vec!['A'; depth + 1];
+                    let total = presses
+                        .chars()
+                        .map(|button| {
+                            let cost = press_cost(keypads, &arms, button);
+                            keypads.press(&mut arms, button).unwrap();
+                            cost
+                        })
+                        .sum::<usize>();
+                    assert_eq!(total, cost, "{} at depth {}", code, depth);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_deep_chains() {
+        let parse = || (Keypad::parse(NUMERIC), Keypad::parse(DIRECTIONAL));
+        let (numeric, directional) = parse();
+        let mut wide: Keypads<u128> = Keypads::new(numeric, directional);
+        let (numeric, directional) = parse();
+        let mut big: Keypads<BigUint> = Keypads::new(numeric, directional);
+        let mut usual = keypads();
+
+        for code in CODES {
+            for depth in 0..=25 {
+                let length = usual.find_shortest_sequence(code, depth, true);
+                assert_eq!(wide.find_shortest_sequence(code, depth, true), length as u128);
+            }
+            // Past 64 bits, and nearly at the end of 128.
+            for depth in [50, 80, 90] {
+                let length = wide.find_shortest_sequence(code, depth, true);
+                assert_eq!(big.find_shortest_sequence(code, depth, true), BigUint::from(length));
+            }
+        }
+
+        // Far enough down, each robot multiplies the length by about 2.488.
+        let deep = big.find_shortest_sequence("029A", 1000, true);
+        let deeper = big.find_shortest_sequence("029A", 1001, true);
+        assert!(deep.bits() > 1300);
+        assert!(BigUint::from(2487u32) * &deep < BigUint::from(1000u32) * &deeper);
+        assert!(BigUint::from(1000u32) * &deeper < BigUint::from(2488u32) * &deep);
+        assert_eq!(big.complexity("029A", 1000), deep * 29u8);
+    }
+
+    #[test]
+    fn test_parsed_paths_match_hand_written_keypad() {
+        // The adjacency list this file used before keypads were parsed.
+        let hand_written = vec![
+            ('7', vec![('4', 'v'), ('8', '>')]),
+            ('8', vec![('5', 'v'), ('9', '>'), ('7', '<')]),
+            ('9', vec![('6', 'v'), ('8', '<')]),
+            ('4', vec![('1', 'v'), ('5', '>'), ('7', '^')]),
+            ('5', vec![('2', 'v'), ('6', '>'), ('4', '<'), ('8', '^')]),
+            ('6', vec![('3', 'v'), ('5', '<'), ('9', '^')]),
+            ('1', vec![('2', '>'), ('4', '^')]),
+            ('2', vec![('3', '>'), ('5', '^'), ('1', '<'), ('0', 'v')]),
+            ('0', vec![('2', '^'), ('A', '>')]),
+            ('3', vec![('6', '^'), ('2', '<'), ('A', 'v')]),
+            ('A', vec![('0', '<'), ('3', '^')]),
+        ]
+        .into_iter()
+        .collect::<HashMap<char, Vec<(char, char)>>>();
+
+        let keypad = Keypad::parse(NUMERIC);
+        assert_eq!(keypad.paths.len(), 121);
+        for ((a, b), paths) in &keypad.paths {
+            let expected = find_shortest_paths(&hand_written, *a, *b);
+            assert_eq!(paths.iter().sorted().collect_vec(), expected.iter().sorted().collect_vec());
+        }
+    }
+
+    #[test]
+    fn test_other_layouts() {
+        // On a phone keypad '1' is in the top corner, three rows above the gap.
+        const PHONE: &str = "123\n456\n789\n 0A";
+        let mut phone: Keypads = Keypads::new(Keypad::parse(PHONE), Keypad::parse(DIRECTIONAL));
+        assert_eq!(phone.find_shortest_sequence("1A", 0, true), 12);
+        assert_eq!(keypads().find_shortest_sequence("1A", 0, true), 8);
+        let paths = &phone.numeric.paths[&('A', '1')];
+        assert_eq!(paths.len(), 9);
+        assert!(!paths.contains(&"<<^^^".to_string()));
+
+        // With 'A' in the middle, every direction is one step from it. The
+        // robot on the numeric keypad moves the same, but the ones behind it
+        // press fewer buttons.
+        const CROSS: &str = " ^ \n<A>\n v ";
+        let mut cross: Keypads = Keypads::new(Keypad::parse(NUMERIC), Keypad::parse(CROSS));
+        let mut usual = keypads();
+        for code in CODES {
+            assert_eq!(cross.find_shortest_sequence(code, 0, true), usual.find_shortest_sequence(code, 0, true));
+            assert!(cross.find_shortest_sequence(code, 2, true) < usual.find_shortest_sequence(code, 2, true));
+        }
+    }
+}
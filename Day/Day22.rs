@@ -1,5 +1,8 @@
+mod day22_xorshift;
+
+use day22_xorshift::Xorshift;
 use rayon::prelude::*;
-use std::cmp::max;
+use std::cmp::{max, Reverse};
 use std::collections::{HashMap, HashSet};
 use std::sync::Mutex;
 use std::time::Instant;
@@ -20,24 +23,128 @@ fn shsb_random(seed: u64) -> u64 {
     seed
 }
 
-fn main() {
-    let secrets = read_lines!("Day7.txt")
-        .map(|line| line.parse::<u64>().unwrap())
-        .collect::<Vec<_>>();
-    
-    // Part 01
-    let now = Instant::now();
-    let part01 = secrets.iter()
-        .map(|secret| (0..2000).fold(*secret, |seed, _| shsb_random(seed)))
-        .sum::<u64>();
-    
-    println!("part01: {} ({:.2?})", part01, now.elapsed());
+// Four price changes, each in -9..=9, packed into one base-19 index with the
+// oldest change in the most significant place.
+const SEQUENCES: usize = 19 * 19 * 19 * 19;
 
-    // Part 02
-    let now = Instant::now();
+// Adds what one buyer pays for each sequence of changes to `totals`: the price
+// the first time the sequence appears. `seen` is a bitset over the sequences,
+// reused between buyers.
+fn add_buyer(secret: u64, totals: &mut [u32], seen: &mut [u64]) {
+    seen.fill(0);
+    let mut seed = secret;
+    let mut previous = seed % 10;
+    let mut index = 0;
+
+    for i in 0..2000 {
+        seed = shsb_random(seed);
+
+        let price = seed % 10;
+        index = (index * 19 + (price + 9 - previous) as usize) % SEQUENCES;
+
+        // The fourth change completes the first window.
+        if i >= 3 && seen[index / 64] & (1 << (index % 64)) == 0 {
+            seen[index / 64] |= 1 << (index % 64);
+            totals[index] += price as u32;
+        }
+
+        previous = price;
+    }
+}
+
+// The four changes packed into `index`, oldest first.
+fn decode(index: usize) -> [i8; 4] {
+    let mut changes = [0; 4];
+    let mut index = index;
+    for change in changes.iter_mut().rev() {
+        *change = (index % 19) as i8 - 9;
+        index /= 19;
+    }
+    changes
+}
+
+// When a buyer's monkey sells: the number of secrets generated so far, and the
+// price it sells at.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Sale {
+    step: usize,
+    price: u64,
+}
+
+// The first time the buyer's prices change by `changes`, if they ever do.
+fn sale(secret: u64, changes: [i8; 4]) -> Option<Sale> {
+    let mut seed = secret;
+    let mut previous = seed % 10;
+    let mut diffs = [0; 4];
+
+    for step in 1..=2000 {
+        seed = shsb_random(seed);
+
+        let price = seed % 10;
+        diffs.rotate_left(1);
+        diffs[3] = price as i8 - previous as i8;
+
+        if step >= 4 && diffs == changes {
+            return Some(Sale { step, price });
+        }
+
+        previous = price;
+    }
+
+    None
+}
+
+// The winning sequence of changes, how many bananas it buys, and where each
+// buyer sells.
+#[derive(Debug)]
+struct Best {
+    total: u64,
+    changes: [i8; 4],
+    sales: Vec<Option<Sale>>,
+}
+
+// The most bananas one sequence of changes can buy. Each rayon worker keeps
+// its own totals, and the workers' totals are summed at the end.
+fn best_sequence(secrets: &[u64]) -> Best {
+    let totals = secrets
+        .par_iter()
+        .fold(
+            || (vec![0; SEQUENCES], vec![0; SEQUENCES.div_ceil(64)]),
+            |(mut totals, mut seen), &secret| {
+                add_buyer(secret, &mut totals, &mut seen);
+                (totals, seen)
+            },
+        )
+        .map(|(totals, _)| totals)
+        .reduce(
+            || vec![0; SEQUENCES],
+            |mut totals, other| {
+                totals.iter_mut().zip(other).for_each(|(total, other)| *total += other);
+                totals
+            },
+        );
+
+    // Ties go to the lowest index, so the answer doesn't depend on the workers.
+    let (index, total) = totals
+        .into_iter()
+        .enumerate()
+        .max_by_key(|&(index, total)| (total, Reverse(index)))
+        .unwrap();
+    let changes = decode(index);
+
+    Best {
+        total: total as u64,
+        changes,
+        sales: secrets.par_iter().map(|&secret| sale(secret, changes)).collect(),
+    }
+}
+
+// The previous search, with one map per buyer and a shared set of sequences
+// already tried. Only kept to compare against in `--bench` and the tests.
+fn best_total_with_maps(secrets: &[u64]) -> u64 {
     let mut sequences = Vec::with_capacity(4);
 
-    for secret in &secrets {
+    for secret in secrets {
         let mut seed = *secret;
         let mut previous = seed % 10;
         let mut diffs = [0; 4];
@@ -50,7 +157,7 @@ fn main() {
             diffs.rotate_right(1);
             diffs[0] = ((val as i64) - (previous as i64)) as i8;
 
-            if i >= 4 {
+            if i >= 3 {
                 map.entry(diffs).or_insert(val);
             }
 
@@ -78,12 +185,61 @@ fn main() {
         }
     }
 
-    println!("part02: {} ({:.2?})", part02, now.elapsed());
+    part02
+}
+
+fn main() {
+    let secrets = read_lines!("Day7.txt")
+        .map(|line| line.parse::<u64>().unwrap())
+        .collect::<Vec<_>>();
+    
+    // Part 01
+    let now = Instant::now();
+    let generator = Xorshift::day22();
+    let part01 = secrets.iter()
+        .map(|&secret| generator.jump(secret, 2000))
+        .sum::<u64>();
+    
+    println!("part01: {} ({:.2?})", part01, now.elapsed());
+
+    // Part 02
+    let now = Instant::now();
+    let part02 = best_sequence(&secrets);
+
+    println!("part02: {} with changes {:?} ({:.2?})", part02.total, part02.changes, now.elapsed());
+
+    if std::env::args().any(|arg| arg == "--report") {
+        for (secret, sale) in secrets.iter().zip(&part02.sales) {
+            match sale {
+                Some(sale) => println!("{}: sells at step {} for {}", secret, sale.step, sale.price),
+                None => println!("{}: never sells", secret),
+            }
+        }
+    }
+
+    if std::env::args().any(|arg| arg == "--generator") {
+        println!(
+            "generator: {} bits, period {}, full period: {}",
+            generator.bits(),
+            generator.period(),
+            generator.is_full_period()
+        );
+        if let Some(&secret) = secrets.first() {
+            println!("{} is on a cycle of {}", secret, generator.cycle_length(secret));
+            println!("{} came 2000 steps after {}", secret, generator.jump_back(secret, 2000));
+        }
+    }
+
+    if std::env::args().any(|arg| arg == "--bench") {
+        let now = Instant::now();
+        let with_maps = best_total_with_maps(&secrets);
+        println!("part02 with maps: {} ({:.2?})", with_maps, now.elapsed());
+    }
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::shsb_random;
+    use crate::*;
 
     #[test]
     fn test_shsb_random() {
@@ -91,4 +247,65 @@ mod tests {
         assert_eq!(shsb_random(15887950), 16495136);
         assert_eq!(shsb_random(16495136), 527345);
     }
+
+    #[test]
+    fn test_inverse_and_jumps() {
+        let generator = Xorshift::day22();
+        for secret in [0, 1, 123, 2024, 16_777_215] {
+            let mut seed = secret;
+            for _ in 0..2000 {
+                let next = shsb_random(seed);
+                assert_eq!(generator.step(seed), next);
+                assert_eq!(generator.step_back(next), seed);
+                seed = next;
+            }
+
+            assert_eq!(generator.jump(secret, 2000), seed);
+            assert_eq!(generator.jump_back(seed, 2000), secret);
+            assert_eq!((0..2000).fold(seed, |seed, _| generator.step_back(seed)), secret);
+
+            for n in [0, 1, 7, 1 << 40, u64::MAX] {
+                assert_eq!(generator.jump_back(generator.jump(secret, n), n), secret);
+            }
+        }
+
+        let sum = [1, 10, 100, 2024].iter().map(|&secret| generator.jump(secret, 2000)).sum::<u64>();
+        assert_eq!(sum, 37327623);
+    }
+
+    #[test]
+    fn test_best_sequence() {
+        let best = best_sequence(&[1, 2, 3, 2024]);
+        assert_eq!((best.total, best.changes), (23, [-2, 1, -1, 3]));
+        assert_eq!(
+            best.sales,
+            [Some(Sale { step: 1964, price: 7 }), Some(Sale { step: 291, price: 7 }), None, Some(Sale { step: 455, price: 9 })]
+        );
+
+        let mut seed = 22;
+        let secrets = (0..300)
+            .map(|_| {
+                seed = shsb_random(seed);
+                seed
+            })
+            .collect::<Vec<_>>();
+        let best = best_sequence(&secrets);
+        assert_eq!(best.total, best_total_with_maps(&secrets));
+        let sold = best.sales.iter().flatten().map(|sale| sale.price).sum::<u64>();
+        assert_eq!(sold, best.total);
+    }
+
+    #[test]
+    fn test_first_window_counts() {
+        // Secret 123 starts with prices 3, 0, 6, 5, 4: the first four changes
+        // are already a window, and selling there is allowed.
+        assert_eq!(sale(123, [-3, 6, -1, -1]), Some(Sale { step: 4, price: 4 }));
+        assert_eq!(sale(123, [-1, -1, 0, 2]), Some(Sale { step: 6, price: 6 }));
+
+        let mut totals = vec![0; SEQUENCES];
+        add_buyer(123, &mut totals, &mut vec![0; SEQUENCES.div_ceil(64)]);
+        let index = [-3, 6, -1, -1].iter().fold(0, |index, &change| index * 19 + (change + 9) as usize);
+        assert_eq!(totals[index], 4);
+        assert_eq!(decode(index), [-3, 6, -1, -1]);
+    }
 }
\ No newline at end of file
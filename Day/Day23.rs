@@ -1,5 +1,5 @@
 use itertools::Itertools;
-use std::collections::{BTreeSet, HashMap, HashSet, VecDeque};
+use std::collections::{BTreeSet, HashMap, HashSet};
 use std::time::Instant;
 
 macro_rules! read_lines {
@@ -10,10 +10,12 @@ macro_rules! read_lines {
     }};
 }
 
-fn main() {
+type Links = HashMap<String, BTreeSet<String>>;
+
+fn parse_links(lines: impl Iterator<Item = String>) -> Links {
     let mut links = HashMap::new();
 
-    for line in read_lines!("Day7.txt") {
+    for line in lines {
         let mut parts = line.split('-');
         let a = parts.next().unwrap().to_string();
         let b = parts.next().unwrap().to_string();
@@ -25,6 +27,100 @@ fn main() {
             .insert(a);
     }
 
+    links
+}
+
+/// Orders computers by repeatedly removing one with the fewest remaining links,
+/// so every computer has few neighbours left later in the order.
+fn degeneracy_order(links: &Links) -> Vec<&str> {
+    let mut degrees: HashMap<&str, usize> = links.iter()
+        .map(|(computer, linked)| (computer.as_str(), linked.len()))
+        .collect();
+    let mut remaining: BTreeSet<(usize, &str)> = degrees.iter()
+        .map(|(&computer, &degree)| (degree, computer))
+        .collect();
+    let mut order = Vec::with_capacity(links.len());
+
+    while let Some((_, computer)) = remaining.pop_first() {
+        degrees.remove(computer);
+        order.push(computer);
+
+        for next in &links[computer] {
+            if let Some(degree) = degrees.get_mut(next.as_str()) {
+                remaining.remove(&(*degree, next.as_str()));
+                *degree -= 1;
+                remaining.insert((*degree, next.as_str()));
+            }
+        }
+    }
+
+    order
+}
+
+/// Bron–Kerbosch with pivoting, keeping only the largest clique seen in `best`.
+fn bron_kerbosch<'a>(
+    links: &'a Links,
+    clique: &mut Vec<&'a str>,
+    mut candidates: BTreeSet<&'a str>,
+    mut excluded: BTreeSet<&'a str>,
+    best: &mut Vec<&'a str>,
+) {
+    if candidates.is_empty() {
+        if excluded.is_empty() && clique.len() > best.len() {
+            *best = clique.clone();
+        }
+        return;
+    }
+    if clique.len() + candidates.len() <= best.len() {
+        return;
+    }
+
+    let pivot = *candidates.union(&excluded)
+        .max_by_key(|&&u| candidates.iter().filter(|&&v| links[u].contains(v)).count())
+        .unwrap();
+    let branches = candidates.iter()
+        .filter(|&&v| !links[pivot].contains(v))
+        .copied()
+        .collect::<Vec<_>>();
+
+    for computer in branches {
+        let linked = &links[computer];
+        let next_candidates = candidates.iter().filter(|&&v| linked.contains(v)).copied().collect();
+        let next_excluded = excluded.iter().filter(|&&v| linked.contains(v)).copied().collect();
+
+        clique.push(computer);
+        bron_kerbosch(links, clique, next_candidates, next_excluded, best);
+        clique.pop();
+
+        candidates.remove(computer);
+        excluded.insert(computer);
+    }
+}
+
+/// Finds the maximum clique: each computer in degeneracy order only branches
+/// into its neighbours that come later, keeping the search sets small.
+fn largest_party(links: &Links) -> BTreeSet<&str> {
+    let order = degeneracy_order(links);
+    let position: HashMap<&str, usize> = order.iter()
+        .enumerate()
+        .map(|(idx, &computer)| (computer, idx))
+        .collect();
+    let mut best = Vec::new();
+
+    for (idx, &computer) in order.iter().enumerate() {
+        let (later, earlier) = links[computer].iter()
+            .map(String::as_str)
+            .partition(|next| position[next] > idx);
+
+        bron_kerbosch(links, &mut vec![computer], later, earlier, &mut best);
+    }
+
+    best.into_iter().collect()
+}
+
+fn main() {
+    let links = parse_links(read_lines!("Day7.txt"));
+
     // Part 01
     let now = Instant::now();
     let pairs = links.iter()
@@ -50,30 +146,55 @@ fn main() {
 
     // Part 02
     let now = Instant::now();
-    let mut marks = HashSet::new();
-    let mut queue = VecDeque::from_iter(links.keys().cloned());
-    let mut groups = Vec::new();
+    let party = largest_party(&links);
 
-    while let Some(computer) = queue.pop_back() {
-        if !marks.insert(computer.clone()) {
-            continue;
-        }
+    println!("part 02: {} ({:.2?})", party.iter().join(","), now.elapsed());
+}
 
-        let linked = links.get(&computer).unwrap();
-        queue.extend(linked.iter().cloned()); // Insert all next computers
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-        for grp in &mut groups {
-            if linked.is_superset(&grp) {
-                grp.insert(computer.clone());
-            }
+    #[test]
+    fn test_example() {
+        let input = "kh-tc qp-kh de-cg ka-co yn-aq qp-ub cg-tb vc-aq tb-ka wh-tc yn-cg kh-ub ta-co \
+            de-co tc-td tb-wq wh-td ta-ka td-qp aq-cg wq-ub ub-vc de-ta wq-aq wq-vc wh-yn ka-de \
+            kh-ta co-tc wh-qp tb-vc td-yn";
+        let links = parse_links(input.split_whitespace().map(String::from));
+
+        assert_eq!(largest_party(&links).iter().join(","), "co,de,ka,ta");
+    }
+
+    /// Builds a clique of `size` computers and, for every `shared`-sized subset
+    /// of it, one extra "z" computer linked to exactly that subset.
+    fn hidden_clique(size: usize, shared: usize) -> Links {
+        let members = (b'a'..).take(size).map(|c| format!("k{}", c as char)).collect::<Vec<_>>();
+        let mut edges = Vec::new();
+
+        for (a, b) in members.iter().tuple_combinations() {
+            edges.push((a.clone(), b.clone()));
+        }
+        for subset in members.iter().combinations(shared) {
+            let z = format!("z{}", subset.iter().map(|m| &m[1..]).join(""));
+            edges.extend(subset.into_iter().map(|m| (z.clone(), m.clone())));
         }
 
-        groups.push(BTreeSet::from([computer]));
+        parse_links(edges.into_iter().map(|(a, b)| format!("{}-{}", a, b)))
     }
 
-    let party = groups.iter()
-        .max_by(|a, b| a.len().cmp(&b.len()))
-        .unwrap();
+    // In both graphs every clique member has a link to a "z" computer that sorts
+    // after it, so whichever member the old greedy walk reaches first is joined
+    // by a "z" next and its group can no longer grow into the full clique.
 
-    println!("part 02: {} ({:.2?})", party.iter().join(","), now.elapsed());
-}
\ No newline at end of file
+    #[test]
+    fn test_clique_hidden_behind_triangles() {
+        let links = hidden_clique(4, 2);
+        assert_eq!(largest_party(&links).iter().join(","), "ka,kb,kc,kd");
+    }
+
+    #[test]
+    fn test_clique_hidden_behind_smaller_cliques() {
+        let links = hidden_clique(6, 4);
+        assert_eq!(largest_party(&links).iter().join(","), "ka,kb,kc,kd,ke,kf");
+    }
+}
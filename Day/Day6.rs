@@ -1,166 +1,223 @@
+use rayon::prelude::*;
 use std::fs::File;
 use std::io::{self, BufReader, BufWriter, Read, Stdout, Write};
-use std::collections::HashSet;
 use std::path::Path;
 
-const DIR8: [(i32, i32); 8] = [
-    (1, 0),
-    (0, 1),
-    (-1, 0),
-    (0, -1),
-    (1, 1),
-    (1, -1),
-    (-1, 1),
-    (-1, -1),
-];
-
-fn solve(input: &str, writer: &mut BufWriter<Stdout>) {
-    let mut map: Vec<Vec<char>> = input
-        .lines()
-        .map(|line| line.chars().collect())
-        .collect();
+/// Up, right, down, left: turning right moves one step along this array.
+const DIRS: [(isize, isize); 4] = [(-1, 0), (0, 1), (1, 0), (0, -1)];
 
-    let mut start: (i32, i32) = (-1, -1);
-    let mut dir: (i32, i32) = (-1, -1);
+/// For each direction and cell, the cell where the guard stops in front of the
+/// next obstacle, or `None` if the guard walks off the map first.
+type Jumps = [Vec<Option<usize>>; 4];
 
-    // Find the starting point and initial direction
-    for i in 0..map.len() {
-        for j in 0..map[0].len() {
-            match map[i][j] {
-                '>' => {
-                    start = (i as i32, j as i32);
-                    dir = (0, 1);
-                }
-                '<' => {
-                    start = (i as i32, j as i32);
-                    dir = (0, -1);
-                }
-                'v' => {
-                    start = (i as i32, j as i32);
-                    dir = (1, 0);
-                }
-                '^' => {
-                    start = (i as i32, j as i32);
-                    dir = (-1, 0);
+/// Fixed-size set of indices packed into 64-bit words.
+struct BitSet {
+    words: Vec<u64>,
+}
+
+impl BitSet {
+    fn new(len: usize) -> Self {
+        BitSet {
+            words: vec![0; len.div_ceil(64)],
+        }
+    }
+
+    /// Adds `idx`, returning `false` if it was already present.
+    fn insert(&mut self, idx: usize) -> bool {
+        let (word, bit) = (idx / 64, 1 << (idx % 64));
+        let fresh = self.words[word] & bit == 0;
+        self.words[word] |= bit;
+        fresh
+    }
+
+    fn contains(&self, idx: usize) -> bool {
+        self.words[idx / 64] & (1 << (idx % 64)) != 0
+    }
+
+    fn clear(&mut self) {
+        self.words.fill(0);
+    }
+}
+
+/// The lab floor as a flat row-major grid.
+struct Map {
+    width: usize,
+    height: usize,
+    blocked: Vec<bool>,
+    start: usize,
+    dir: usize,
+}
+
+impl Map {
+    fn parse(input: &str) -> Self {
+        let rows: Vec<&[u8]> = input.lines().map(str::as_bytes).collect();
+        let (height, width) = (rows.len(), rows[0].len());
+        let mut blocked = Vec::with_capacity(width * height);
+        let (mut start, mut dir) = (0, 0);
+
+        for (i, row) in rows.iter().enumerate() {
+            for (j, &cell) in row.iter().enumerate() {
+                blocked.push(cell == b'#');
+                if let Some(d) = b"^>v<".iter().position(|&c| c == cell) {
+                    start = i * width + j;
+                    dir = d;
                 }
-                _ => (),
             }
         }
+
+        Map {
+            width,
+            height,
+            blocked,
+            start,
+            dir,
+        }
     }
 
-    // Mark initial visited positions
-    let mut initial_visited: Vec<Vec<bool>> = vec![vec![false; map[0].len()]; map.len()];
-    let mut initial_dir = dir;
-    travel_map(
-        start.0,
-        start.1,
-        &mut initial_dir,
-        &map,
-        &mut initial_visited,
-    );
-
-    let mut cycles: i64 = 0;
-    for i in 0..initial_visited.len() {
-        for j in 0..initial_visited[0].len() {
-            if (i as i32, j as i32) == start || map[i][j] != '.' {
-                continue;
-            }
+    /// Cell one step from `cell` in direction `dir`, or `None` off the map.
+    fn step(&self, cell: usize, dir: usize) -> Option<usize> {
+        let (dx, dy) = DIRS[dir];
+        let x = (cell / self.width).checked_add_signed(dx)?;
+        let y = (cell % self.width).checked_add_signed(dy)?;
+        (x < self.height && y < self.width).then(|| x * self.width + y)
+    }
 
-            let mut is_surrounding = false;
-            for &(dx, dy) in DIR8.iter() {
-                let (x, y) = ((i as i32) + dx, (j as i32) + dy);
-                if x >= 0
-                    && x < initial_visited.len() as i32
-                    && y >= 0
-                    && y < initial_visited[0].len() as i32
-                    && initial_visited[x as usize][y as usize]
-                {
-                    is_surrounding = true;
-                    break;
-                }
+    /// Walks the guard from the start, recording each `(cell, direction)` state
+    /// in `seen` as `cell * 4 + direction`. Returns `true` if the guard repeats a
+    /// state, i.e. is stuck in a loop, and `false` once the guard walks off the
+    /// map.
+    fn walk(&self, seen: &mut BitSet) -> bool {
+        let (mut cell, mut dir) = (self.start, self.dir);
+
+        loop {
+            if !seen.insert(cell * 4 + dir) {
+                return true;
             }
-            if !is_surrounding {
-                continue;
+            match self.step(cell, dir) {
+                None => return false,
+                Some(next) if self.blocked[next] => dir = (dir + 1) % 4,
+                Some(next) => cell = next,
             }
+        }
+    }
+
+    fn jumps(&self) -> Jumps {
+        let cells = self.blocked.len();
+        let mut jumps: Jumps = Default::default();
 
-            map[i][j] = '#';
-
-            let mut cur_visited: HashSet<(usize, usize, i32, i32)> = HashSet::new();
-            let mut cur_dir = dir;
-            let mut has_cycle = false;
-
-            check_cycle_in_map(
-                start.0,
-                start.1,
-                &mut cur_dir,
-                &map,
-                &mut cur_visited,
-                &mut has_cycle,
-            );
-            if has_cycle {
-                cycles += 1;
+        for (dir, table) in jumps.iter_mut().enumerate() {
+            *table = vec![None; cells];
+
+            // The cell ahead must be filled in first: up and left look at
+            // lower indices, right and down at higher ones.
+            let order: Box<dyn Iterator<Item = usize>> = match dir {
+                0 | 3 => Box::new(0..cells),
+                _ => Box::new((0..cells).rev()),
+            };
+            for cell in order {
+                table[cell] = match self.step(cell, dir) {
+                    None => None,
+                    Some(next) if self.blocked[next] => Some(cell),
+                    Some(next) => table[next],
+                };
             }
+        }
 
-            map[i][j] = '.';
+        jumps
+    }
+
+    /// Number of steps from `from` to `to` going in `dir`, if `to` lies ahead.
+    fn distance(&self, from: usize, to: usize, dir: usize) -> Option<usize> {
+        let (fx, fy) = (from / self.width, from % self.width);
+        let (tx, ty) = (to / self.width, to % self.width);
+        match dir {
+            0 if fy == ty && tx < fx => Some(fx - tx),
+            1 if fx == tx && ty > fy => Some(ty - fy),
+            2 if fy == ty && tx > fx => Some(tx - fx),
+            3 if fx == tx && ty < fy => Some(fy - ty),
+            _ => None,
         }
     }
 
-    writeln!(writer, "{}", cycles).ok();
-}
+    /// Same as `walk` with an extra obstacle at `obstacle`, but jumping straight
+    /// from turn to turn and only recording the states where the guard turns.
+    fn loops_with(&self, jumps: &Jumps, obstacle: usize, seen: &mut BitSet) -> bool {
+        let (mut cell, mut dir) = (self.start, self.dir);
 
-fn check_cycle_in_map(
-    x: i32,
-    y: i32,
-    dir: &mut (i32, i32),
-    map: &Vec<Vec<char>>,
-    visited: &mut HashSet<(usize, usize, i32, i32)>,
-    has_cycle: &mut bool,
-) {
-    if !visited.contains(&(x as usize, y as usize, dir.0, dir.1)) {
-        visited.insert((x as usize, y as usize, dir.0, dir.1));
-    } else {
-        *has_cycle = true;
-        return;
-    }
-
-    let (mut nx, mut ny) = (x + dir.0, y + dir.1);
-    if nx >= 0 && nx < map.len() as i32 && ny >= 0 && ny < map[0].len() as i32 {
-        if map[nx as usize][ny as usize] == '#' {
-            if dir.0 != 0 {
-                dir.1 = -dir.0;
-                dir.0 = 0;
-            } else {
-                dir.0 = dir.1;
-                dir.1 = 0;
+        loop {
+            if !seen.insert(cell * 4 + dir) {
+                return true;
             }
-            (nx, ny) = (x, y);
+
+            let stop = jumps[dir][cell];
+            let blocked_by_obstacle = self.distance(cell, obstacle, dir).filter(|&steps| {
+                stop.is_none_or(|stop| steps <= self.distance(cell, stop, dir).unwrap_or(0))
+            });
+
+            cell = match (blocked_by_obstacle, stop) {
+                (Some(steps), _) => {
+                    let (dx, dy) = DIRS[dir];
+                    let delta = dx * self.width as isize + dy;
+                    cell.wrapping_add_signed(delta * (steps as isize - 1))
+                }
+                (None, Some(stop)) => stop,
+                (None, None) => return false,
+            };
+            dir = (dir + 1) % 4;
         }
-        check_cycle_in_map(nx, ny, dir, map, visited, has_cycle);
     }
 }
 
-fn travel_map(
-    x: i32,
-    y: i32,
-    dir: &mut (i32, i32),
-    map: &Vec<Vec<char>>,
-    visited: &mut Vec<Vec<bool>>,
-) {
-    visited[x as usize][y as usize] = true;
-
-    let (mut nx, mut ny) = (x + dir.0, y + dir.1);
-    if nx >= 0 && nx < map.len() as i32 && ny >= 0 && ny < map[0].len() as i32 {
-        if map[nx as usize][ny as usize] == '#' {
-            if dir.0 != 0 {
-                dir.1 = -dir.0;
-                dir.0 = 0;
-            } else {
-                dir.0 = dir.1;
-                dir.1 = 0;
-            }
-            (nx, ny) = (x, y);
-        }
-        travel_map(nx, ny, dir, map, visited);
+/// Outcome of the guard's patrol, with the cells behind both answers.
+struct Patrol {
+    /// Cells the guard walks over before leaving the map, in row-major order.
+    visited: Vec<(usize, usize)>,
+    /// Cells where a single new obstruction traps the guard in a loop.
+    loop_obstructions: Vec<(usize, usize)>,
+}
+
+fn solve(input: &str) -> Patrol {
+    let map = Map::parse(input);
+    let mut seen = BitSet::new(map.blocked.len() * 4);
+    map.walk(&mut seen);
+
+    // Only cells on the original path can change where the guard goes.
+    let path: Vec<usize> = (0..map.blocked.len())
+        .filter(|&cell| (0..4).any(|dir| seen.contains(cell * 4 + dir)))
+        .collect();
+
+    let jumps = map.jumps();
+    let loop_obstructions = path
+        .par_iter()
+        .filter(|&&cell| cell != map.start)
+        .map_init(
+            || BitSet::new(map.blocked.len() * 4),
+            |seen, &cell| {
+                seen.clear();
+                map.loops_with(&jumps, cell, seen).then_some(cell)
+            },
+        )
+        .flatten()
+        .map(|cell| (cell / map.width, cell % map.width))
+        .collect();
+
+    Patrol {
+        visited: path
+            .into_iter()
+            .map(|cell| (cell / map.width, cell % map.width))
+            .collect(),
+        loop_obstructions,
+    }
+}
+
+fn write_report(patrol: &Patrol, writer: &mut BufWriter<Stdout>) {
+    writeln!(writer, "visited cells (row, col):").ok();
+    for (i, j) in &patrol.visited {
+        writeln!(writer, "{} {}", i, j).ok();
+    }
+    writeln!(writer, "loop obstructions (row, col):").ok();
+    for (i, j) in &patrol.loop_obstructions {
+        writeln!(writer, "{} {}", i, j).ok();
     }
 }
 
@@ -172,5 +229,103 @@ fn main() {
     reader.read_to_string(&mut input).expect("Failed to read file");
 
     let writer = &mut BufWriter::new(io::stdout());
-    solve(&input, writer);
+    let patrol = solve(&input);
+    writeln!(writer, "Part 1: {}", patrol.visited.len()).ok();
+    writeln!(writer, "Part 2: {}", patrol.loop_obstructions.len()).ok();
+
+    if std::env::args().any(|arg| arg == "--report") {
+        write_report(&patrol, writer);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EXAMPLE: &str = "\
+....#.....
+.........#
+..........
+..#.......
+.......#..
+..........
+.#..^.....
+........#.
+#.........
+......#...";
+
+    #[test]
+    fn test_example() {
+        let patrol = solve(EXAMPLE);
+        assert_eq!(patrol.visited.len(), 41);
+        assert_eq!(
+            patrol.loop_obstructions,
+            vec![(6, 3), (7, 6), (7, 7), (8, 1), (8, 3), (9, 7)]
+        );
+    }
+
+    #[test]
+    fn test_long_walk() {
+        // A million-cell column: the old recursive walk overflowed the stack here.
+        let mut input = ".\n".repeat(1_000_000);
+        input.push('^');
+        let map = Map::parse(&input);
+        let mut seen = BitSet::new(map.blocked.len() * 4);
+
+        assert!(!map.walk(&mut seen));
+        assert!((0..map.blocked.len()).all(|cell| seen.contains(cell * 4)));
+    }
+
+    #[test]
+    fn test_jumps_match_step_by_step_walk() {
+        // Dense corners, a guard next to the edge and a wide open room.
+        let maps = [
+            EXAMPLE,
+            "\
+.#...#....
+.....#..#.
+#..#......
+....^...#.
+.#........
+......#..#
+..#.......
+.....#.#..",
+            "\
+...#.....
+#......#.
+.......#.
+.#.......
+^.....#..
+......#..",
+            "\
+..#...........#
+#.....#........
+.........#.....
+......^........
+.#.............
+.....#.......#.
+..#.......#....",
+        ];
+
+        for input in maps {
+            let mut map = Map::parse(input);
+            let mut seen = BitSet::new(map.blocked.len() * 4);
+            assert!(!map.walk(&mut seen), "{}", input);
+
+            let mut expected = Vec::new();
+            for cell in 0..map.blocked.len() {
+                if cell == map.start || map.blocked[cell] {
+                    continue;
+                }
+                map.blocked[cell] = true;
+                seen.clear();
+                if map.walk(&mut seen) {
+                    expected.push((cell / map.width, cell % map.width));
+                }
+                map.blocked[cell] = false;
+            }
+
+            assert_eq!(solve(input).loop_obstructions, expected, "{}", input);
+        }
+    }
 }
@@ -1,38 +1,49 @@
-use std::collections::HashSet;
 use std::fs::File;
 use std::io::{self, BufRead};
 use std::path::Path;
 
-fn evaluate_left_to_right(nums: &[i64], ops: &[char]) -> i64 {
-    let mut result = nums[0];
-    for (i, &op) in ops.iter().enumerate() {
-        match op {
-            '+' => result += nums[i + 1],
-            '*' => result *= nums[i + 1],
-            _ => unreachable!(),
-        }
+/// Smallest power of ten above `n`, i.e. what `a || n` multiplies `a` by.
+fn concat_base(n: i64) -> i64 {
+    let mut base = 10;
+    while base <= n {
+        base *= 10;
     }
-    result
+    base
 }
 
-fn generate_all_operations(nums: &[i64], target: i64) -> bool {
-    let n = nums.len() - 1; 
-    let ops = ['+', '*'];
-    let mut valid = false;
-    for mask in 0..(1 << n) {
-        let mut current_ops = vec![];
-        for i in 0..n {
-            current_ops.push(ops[(mask >> i) & 1]);
-        }
-        if evaluate_left_to_right(nums, &current_ops) == target {
-            valid = true;
-            break;
+/// Works backwards from the target: the last number must have been added to,
+/// multiplied onto or concatenated after the value of the numbers before it,
+/// so each operator is only tried when it can be undone exactly.
+fn can_produce(target: i64, nums: &[i64], concat: bool) -> bool {
+    // An equation with no numbers can't produce anything.
+    let Some((&last, rest)) = nums.split_last() else {
+        return false;
+    };
+    if rest.is_empty() {
+        return target == last;
+    }
+
+    if target >= last && can_produce(target - last, rest, concat) {
+        return true;
+    }
+    if last != 0 && target % last == 0 && can_produce(target / last, rest, concat) {
+        return true;
+    }
+    // Anything the numbers before make, times 0, is 0.
+    if last == 0 && target == 0 {
+        return true;
+    }
+    if concat {
+        let base = concat_base(last);
+        if target % base == last && can_produce(target / base, rest, concat) {
+            return true;
         }
     }
-    valid
+
+    false
 }
 
-fn total_calibration_result(input: &str) -> i64 {
+fn total_calibration_result(input: &str, concat: bool) -> i64 {
     let mut total = 0;
 
     for line in input.lines() {
@@ -40,7 +51,7 @@ fn total_calibration_result(input: &str) -> i64 {
             let target: i64 = target.parse().unwrap();
             let nums: Vec<i64> = numbers.split_whitespace().map(|n| n.parse().unwrap()).collect();
 
-            if generate_all_operations(&nums, target) {
+            if can_produce(target, &nums, concat) {
                 total += target;
             }
         }
@@ -65,11 +76,51 @@ fn main() {
     let file_path = "Day7.txt"; 
     match read_file(file_path) {
         Ok(input) => {
-            let result = total_calibration_result(&input);
+            let result = total_calibration_result(&input, false);
             println!("Total calibration result: {}", result);
+            let result = total_calibration_result(&input, true);
+            println!("Total calibration result with concatenation: {}", result);
         }
         Err(e) => {
             eprintln!("Error reading file: {}", e);
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EXAMPLE: &str = "\
+190: 10 19
+3267: 81 40 27
+83: 17 5
+156: 15 6
+7290: 6 8 6 15
+161011: 16 10 13
+192: 17 8 14
+21037: 9 7 18 13
+292: 11 6 16 20";
+
+    #[test]
+    fn test_example() {
+        assert_eq!(total_calibration_result(EXAMPLE, false), 3749);
+        assert_eq!(total_calibration_result(EXAMPLE, true), 11387);
+    }
+
+    #[test]
+    fn test_concatenation() {
+        assert!(can_produce(156, &[15, 6], true));
+        assert!(can_produce(1000, &[10, 0, 0], true));
+        assert!(!can_produce(156, &[15, 6], false));
+        assert!(!can_produce(1506, &[15, 6], true));
+        assert!(can_produce(0, &[5, 0], false));
+        assert!(can_produce(0, &[3, 4, 0], true));
+    }
+
+    #[test]
+    fn test_no_operands() {
+        assert!(!can_produce(0, &[], true));
+        assert_eq!(total_calibration_result("7: \n190: 10 19\n", false), 190);
+    }
+}
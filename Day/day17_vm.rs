@@ -0,0 +1,431 @@
+//! The three-bit computer from day 17, shared by both parts.
+
+pub mod asm;
+pub mod compile;
+pub mod debugger;
+pub mod disasm;
+pub mod quine;
+pub mod word;
+
+pub use word::Word;
+
+/// Registers A, B and C, in that order.
+pub type Registers<W = usize> = [W; 3];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Instruction {
+    Adv, // 0
+    Bxl, // 1
+    Bst, // 2
+    Jnz, // 3
+    Bxc, // 4
+    Out, // 5
+    Bdv, // 6
+    Cdv, // 7
+}
+
+impl TryFrom<u8> for Instruction {
+    type Error = u8;
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        Ok(match value {
+            0 => Self::Adv,
+            1 => Self::Bxl,
+            2 => Self::Bst,
+            3 => Self::Jnz,
+            4 => Self::Bxc,
+            5 => Self::Out,
+            6 => Self::Bdv,
+            7 => Self::Cdv,
+            _ => return Err(value),
+        })
+    }
+}
+
+impl Instruction {
+    pub fn mnemonic(self) -> &'static str {
+        match self {
+            Self::Adv => "adv",
+            Self::Bxl => "bxl",
+            Self::Bst => "bst",
+            Self::Jnz => "jnz",
+            Self::Bxc => "bxc",
+            Self::Out => "out",
+            Self::Bdv => "bdv",
+            Self::Cdv => "cdv",
+        }
+    }
+
+    /// Whether the operand is a combo operand rather than a literal.
+    pub fn takes_combo(self) -> bool {
+        matches!(self, Self::Adv | Self::Bst | Self::Out | Self::Bdv | Self::Cdv)
+    }
+}
+
+/// What the machine does when it reads an opcode outside `0..=7`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InvalidOpcode {
+    Halt,
+    Panic,
+}
+
+/// Why the machine stopped.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Halt {
+    /// The instruction pointer ran past the end of the program.
+    End,
+    /// An opcode outside `0..=7`, with `InvalidOpcode::Halt` configured.
+    InvalidOpcode(u8),
+    /// The output sink asked to stop.
+    Sink,
+    /// The configured step limit was reached.
+    StepLimit,
+}
+
+/// Receives the values written by `out` instructions.
+pub trait Output {
+    /// Takes one value; returning `false` halts the machine after this instruction.
+    fn write(&mut self, value: u8) -> bool;
+}
+
+impl Output for Vec<u8> {
+    fn write(&mut self, value: u8) -> bool {
+        self.push(value);
+        true
+    }
+}
+
+impl<F: FnMut(u8) -> bool> Output for F {
+    fn write(&mut self, value: u8) -> bool {
+        self(value)
+    }
+}
+
+pub struct Vm<'a, W = usize> {
+    pub registers: Registers<W>,
+    pub ip: usize,
+    /// Instructions executed so far.
+    pub steps: usize,
+    program: &'a [u8],
+    invalid_opcode: InvalidOpcode,
+    step_limit: Option<usize>,
+}
+
+impl<'a, W: Word> Vm<'a, W> {
+    pub fn new(registers: Registers<W>, program: &'a [u8]) -> Self {
+        Self {
+            registers,
+            ip: 0,
+            steps: 0,
+            program,
+            invalid_opcode: InvalidOpcode::Halt,
+            step_limit: None,
+        }
+    }
+
+    pub fn on_invalid_opcode(mut self, action: InvalidOpcode) -> Self {
+        self.invalid_opcode = action;
+        self
+    }
+
+    pub fn with_step_limit(mut self, limit: usize) -> Self {
+        self.step_limit = Some(limit);
+        self
+    }
+
+    pub fn program(&self) -> &'a [u8] {
+        self.program
+    }
+
+    pub fn combo(&self, operand: u8) -> W {
+        match operand {
+            0..=3 => W::from_u8(operand),    // Literal values 0-3
+            4 => self.registers[0].clone(),  // Register A
+            5 => self.registers[1].clone(),  // Register B
+            6 => self.registers[2].clone(),  // Register C
+            _ => panic!("Invalid combo operand: {}", operand),
+        }
+    }
+
+    /// Executes one instruction, returning why the machine stopped if it did.
+    pub fn step(&mut self, out: &mut impl Output) -> Option<Halt> {
+        if self.ip + 1 >= self.program.len() {
+            return Some(Halt::End);
+        }
+        if self.step_limit.is_some_and(|limit| self.steps >= limit) {
+            return Some(Halt::StepLimit);
+        }
+
+        let opcode = self.program[self.ip];
+        let operand = self.program[self.ip + 1];
+        let instruction = match Instruction::try_from(opcode) {
+            Ok(instruction) => instruction,
+            Err(opcode) if self.invalid_opcode == InvalidOpcode::Halt => {
+                return Some(Halt::InvalidOpcode(opcode))
+            }
+            Err(opcode) => panic!("Invalid opcode: {}", opcode),
+        };
+
+        let ip = self.ip;
+        let value = match instruction.takes_combo() {
+            true => self.combo(operand),
+            false => W::from_u8(operand),
+        };
+        let mut halt = None;
+        self.steps += 1;
+        self.ip += 2;
+
+        match instruction {
+            Instruction::Adv => {
+                self.registers[0] = self.registers[0].shr(&value);
+            }
+            Instruction::Bxl => {
+                self.registers[1] = self.registers[1].xor(&value);
+            }
+            Instruction::Bst => {
+                self.registers[1] = W::from_u8(value.low3());
+            }
+            Instruction::Jnz => {
+                if !self.registers[0].is_zero() {
+                    self.ip = operand as usize;
+                }
+            }
+            Instruction::Bxc => {
+                self.registers[1] = self.registers[1].xor(&self.registers[2]);
+            }
+            Instruction::Out => {
+                if !out.write(value.low3()) {
+                    halt = Some(Halt::Sink);
+                }
+            }
+            Instruction::Bdv => {
+                self.registers[1] = self.registers[0].shr(&value);
+            }
+            Instruction::Cdv => {
+                self.registers[2] = self.registers[0].shr(&value);
+            }
+        }
+
+        tracing::trace!(
+            ip,
+            opcode,
+            operand,
+            combo = instruction.takes_combo().then(|| tracing::field::display(&value)),
+            a = %self.registers[0],
+            b = %self.registers[1],
+            c = %self.registers[2],
+            "{}",
+            instruction.mnemonic()
+        );
+
+        halt
+    }
+
+    pub fn run(&mut self, out: &mut impl Output) -> Halt {
+        loop {
+            if let Some(halt) = self.step(out) {
+                return halt;
+            }
+        }
+    }
+}
+
+/// Runs `program` to the end and collects its output.
+pub fn run<W: Word>(registers: Registers<W>, program: &[u8]) -> Vec<u8> {
+    let mut output = Vec::new();
+    Vm::new(registers, program).run(&mut output);
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use num_bigint::BigUint;
+
+    // The two implementations this module replaced, kept verbatim apart from
+    // their output plumbing so the shared VM can be checked against both.
+
+    fn old_run(mut registers: Registers, program: &[u8]) -> Vec<u8> {
+        let combo = |registers: &Registers, operand: u8| match operand {
+            0..=3 => operand as usize,
+            4 => registers[0],
+            5 => registers[1],
+            6 => registers[2],
+            _ => panic!("Invalid combo operand: {}", operand),
+        };
+        let mut output = Vec::new();
+        let mut ip = 0;
+
+        while ip < program.len() {
+            let opcode = program[ip];
+            let operand = program[ip + 1];
+            ip += 2;
+
+            match opcode {
+                0 => registers[0] /= 2_usize.pow(combo(&registers, operand) as u32),
+                1 => registers[1] ^= operand as usize,
+                2 => registers[1] = combo(&registers, operand) % 8,
+                3 => {
+                    if registers[0] != 0 {
+                        ip = operand as usize;
+                    }
+                }
+                4 => registers[1] ^= registers[2],
+                5 => output.push((combo(&registers, operand) % 8) as u8),
+                6 => registers[1] = registers[0] / 2_usize.pow(combo(&registers, operand) as u32),
+                7 => registers[2] = registers[0] / 2_usize.pow(combo(&registers, operand) as u32),
+                _ => panic!("Invalid opcode: {}", opcode),
+            }
+        }
+
+        output
+    }
+
+    fn old_computer(registers: Registers, program: &[u8]) -> Vec<u8> {
+        let [mut a, mut b, mut c] = registers;
+        let combo = |a: usize, b: usize, c: usize, operand: u8| match operand {
+            0..=3 => operand as usize,
+            4 => a,
+            5 => b,
+            6 => c,
+            _ => panic!("Invalid combo operand"),
+        };
+        let mut output = Vec::new();
+        let mut ip = 0;
+
+        while ip < program.len() {
+            let opcode = program[ip];
+            let operand = program[ip + 1];
+            match opcode {
+                0 => a >>= combo(a, b, c, operand),
+                1 => b ^= operand as usize,
+                2 => b = combo(a, b, c, operand) % 8,
+                3 => {
+                    if a != 0 {
+                        ip = operand as usize;
+                        continue;
+                    }
+                }
+                4 => b ^= c,
+                5 => output.push((combo(a, b, c, operand) % 8) as u8),
+                6 => b = a >> combo(a, b, c, operand),
+                7 => c = a >> combo(a, b, c, operand),
+                _ => break,
+            }
+            ip += 2;
+        }
+
+        output
+    }
+
+    pub(super) struct Lcg(pub(super) u64);
+
+    impl Lcg {
+        pub(super) fn next(&mut self, bound: u64) -> u64 {
+            self.0 = self.0.wrapping_mul(6_364_136_223_846_793_005).wrapping_add(1_442_695_040_888_963_407);
+            (self.0 >> 33) % bound
+        }
+    }
+
+    /// A random loop body ending in `jnz 0`. Shifts only use literal operands or
+    /// B right after `bst`, so neither old implementation can overflow, and a
+    /// forced `adv` with a non-zero literal makes every loop terminate.
+    pub(super) fn random_program(rng: &mut Lcg) -> Vec<u8> {
+        let mut chunks: Vec<Vec<u8>> = (0..rng.next(8))
+            .map(|_| match rng.next(7) {
+                0 => vec![0, rng.next(4) as u8],
+                1 => vec![1, rng.next(8) as u8],
+                2 => vec![2, rng.next(7) as u8],
+                3 => vec![4, rng.next(8) as u8],
+                4 => vec![5, rng.next(7) as u8],
+                5 => vec![2, rng.next(7) as u8, 6, 5],
+                _ => vec![2, rng.next(7) as u8, 7, 5],
+            })
+            .collect();
+        let forced = rng.next(chunks.len() as u64 + 1) as usize;
+        chunks.insert(forced, vec![0, 1 + rng.next(3) as u8]);
+        chunks.push(vec![3, 0]);
+        chunks.concat()
+    }
+
+    #[test]
+    fn test_matches_old_implementations() {
+        let mut rng = Lcg(17);
+        for _ in 0..2_000 {
+            let program = random_program(&mut rng);
+            let registers = [rng.next(1 << 40) as usize, rng.next(1 << 20) as usize, rng.next(1 << 20) as usize];
+            let output = run(registers, &program);
+
+            assert_eq!(output, old_run(registers, &program), "{:?} {:?}", registers, program);
+            assert_eq!(output, old_computer(registers, &program), "{:?} {:?}", registers, program);
+            assert_eq!(output, run(registers.map(|r| r as u128), &program));
+            assert_eq!(output, run(registers.map(BigUint::from), &program));
+        }
+    }
+
+    #[test]
+    fn test_invalid_opcode_halts_like_computer() {
+        let program = [5, 4, 9, 0, 5, 4];
+        let mut output = Vec::new();
+        let halt = Vm::new([3usize, 0, 0], &program).run(&mut output);
+
+        assert_eq!(halt, Halt::InvalidOpcode(9));
+        assert_eq!(output, old_computer([3, 0, 0], &program));
+    }
+
+    #[test]
+    #[should_panic(expected = "Invalid opcode: 9")]
+    fn test_invalid_opcode_panics_like_run() {
+        let program = [5, 4, 9, 0, 5, 4];
+        Vm::new([3usize, 0, 0], &program)
+            .on_invalid_opcode(InvalidOpcode::Panic)
+            .run(&mut Vec::new());
+    }
+
+    #[test]
+    fn test_large_shift_clears_register() {
+        assert_eq!(run([usize::MAX, 70, 0], &[0, 5, 5, 4]), vec![0]);
+    }
+
+    #[test]
+    fn test_wide_registers() {
+        // `adv 3`, `out A`, `jnz 0` prints A three bits at a time, so every
+        // bit of a value too wide for `usize` must survive.
+        let program = [0, 3, 5, 4, 3, 0];
+        let digits = |mut a: u128| {
+            let mut digits = Vec::new();
+            while a != 0 {
+                a >>= 3;
+                digits.push((a % 8) as u8);
+            }
+            digits
+        };
+        let a = u128::MAX / 3;
+        assert_eq!(run([a, 0, 0], &program), digits(a));
+        assert_eq!(run([BigUint::from(a), BigUint::default(), BigUint::default()], &program), digits(a));
+
+        let huge = BigUint::from(7u8) << 1000u32;
+        let output = run([huge, BigUint::default(), BigUint::default()], &program);
+        assert_eq!(output.len(), 335);
+        assert_eq!(output[332..], [6, 1, 0]);
+
+        // Shifting by a register larger than any width clears the result.
+        let big = BigUint::from(1u8) << 200u32;
+        assert_eq!(run([big.clone(), big, BigUint::default()], &[0, 5, 5, 4]), vec![0]);
+    }
+
+    #[test]
+    fn test_sink_and_step_limit() {
+        let program = [0, 1, 5, 4, 3, 0];
+        let mut first = None;
+        let halt = Vm::new([6usize, 0, 0], &program).run(&mut |value| {
+            first = Some(value);
+            false
+        });
+        assert_eq!((halt, first), (Halt::Sink, Some(3)));
+
+        let mut vm = Vm::new([1usize << 40, 0, 0], &program).with_step_limit(10);
+        assert_eq!(vm.run(&mut Vec::new()), Halt::StepLimit);
+        assert_eq!(vm.steps, 10);
+    }
+}
@@ -0,0 +1,182 @@
+//! Assembler from mnemonics (`adv 3`, `out A`, `jnz loop`, ...) to the program
+//! bytes that `parse_input` produces.
+//!
+//! Each line holds at most one instruction, optionally preceded by `label:`
+//! definitions. Everything after `;` is a comment. Combo operands are `0`-`3`,
+//! `A`, `B` or `C` (raw `4`-`6` also work), literal operands are `0`-`7`, and
+//! `jnz` also takes a label. `bxc` ignores its operand, which defaults to 0.
+
+use super::Instruction;
+use std::collections::HashMap;
+use std::fmt;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AsmError {
+    /// 1-based source line.
+    pub line: usize,
+    pub message: String,
+}
+
+impl fmt::Display for AsmError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+impl std::error::Error for AsmError {}
+
+fn is_label(name: &str) -> bool {
+    name.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_')
+        && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+fn combo_operand(token: &str) -> Result<u8, String> {
+    match token {
+        "A" => Ok(4),
+        "B" => Ok(5),
+        "C" => Ok(6),
+        "7" => Err("combo operand 7 is reserved and does not appear in valid programs".to_string()),
+        _ => match token.parse::<u8>() {
+            Ok(value @ 0..=6) => Ok(value),
+            _ => Err(format!("`{}` is not a combo operand (0-3, A, B or C)", token)),
+        },
+    }
+}
+
+fn literal_operand(token: &str) -> Result<u8, String> {
+    match token.parse::<u8>() {
+        Ok(value @ 0..=7) => Ok(value),
+        _ => Err(format!("`{}` is not a literal operand (0-7)", token)),
+    }
+}
+
+pub fn assemble(source: &str) -> Result<Vec<u8>, AsmError> {
+    let mut labels = HashMap::new();
+    let mut instructions = Vec::new();
+
+    for (idx, line) in source.lines().enumerate() {
+        let line_no = idx + 1;
+        let error = |message: String| AsmError {
+            line: line_no,
+            message,
+        };
+        let mut rest = line.split(';').next().unwrap().trim();
+
+        while let Some((name, after)) = rest.split_once(':') {
+            let name = name.trim();
+            if !is_label(name) {
+                return Err(error(format!("`{}` is not a valid label", name)));
+            }
+            if labels.insert(name.to_string(), instructions.len() * 2).is_some() {
+                return Err(error(format!("label `{}` is defined twice", name)));
+            }
+            rest = after.trim();
+        }
+        if rest.is_empty() {
+            continue;
+        }
+
+        let mut tokens = rest.split_whitespace();
+        let mnemonic = tokens.next().unwrap();
+        let operand = tokens.next();
+        if let Some(extra) = tokens.next() {
+            return Err(error(format!("unexpected `{}` after the operand", extra)));
+        }
+
+        let instruction = (0..8)
+            .filter_map(|opcode| Instruction::try_from(opcode).ok())
+            .find(|instruction| instruction.mnemonic() == mnemonic.to_ascii_lowercase())
+            .ok_or_else(|| error(format!("unknown instruction `{}`", mnemonic)))?;
+        if operand.is_none() && instruction != Instruction::Bxc {
+            return Err(error(format!("`{}` needs an operand", mnemonic)));
+        }
+
+        instructions.push((line_no, instruction, operand));
+    }
+
+    let mut program = Vec::with_capacity(instructions.len() * 2);
+    for (line, instruction, operand) in instructions {
+        let error = |message: String| AsmError { line, message };
+        let operand = match (instruction, operand) {
+            (_, None) => 0,
+            (Instruction::Jnz, Some(token)) if is_label(token) => match labels.get(token) {
+                Some(&address) if address <= 7 => address as u8,
+                Some(&address) => {
+                    return Err(error(format!(
+                        "label `{}` is at address {}, out of reach of a 3-bit jump",
+                        token, address
+                    )))
+                }
+                None => return Err(error(format!("unknown label `{}`", token))),
+            },
+            (_, Some(token)) if instruction.takes_combo() => combo_operand(token).map_err(error)?,
+            (_, Some(token)) => literal_operand(token).map_err(error)?,
+        };
+        program.extend([instruction as u8, operand]);
+    }
+
+    Ok(program)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::disasm::disassemble;
+    use super::super::tests::Lcg;
+    use super::*;
+
+    #[test]
+    fn test_assemble() {
+        let source = "
+            ; Day 17 input
+            start: bst A
+                   bxl 7
+                   cdv B
+                   bxl 7
+                   adv 3   ; next three bits
+                   bxc 1
+                   out B
+                   jnz start
+        ";
+        assert_eq!(assemble(source).unwrap(), vec![2, 4, 1, 7, 7, 5, 1, 7, 0, 3, 4, 1, 5, 5, 3, 0]);
+        assert_eq!(assemble("a: b:\nBXC\nout 5\njnz b").unwrap(), vec![4, 0, 5, 5, 3, 0]);
+    }
+
+    #[test]
+    fn test_errors() {
+        let error = |source| assemble(source).unwrap_err().to_string();
+
+        assert_eq!(
+            error("adv 3\nout 7"),
+            "line 2: combo operand 7 is reserved and does not appear in valid programs"
+        );
+        assert_eq!(error("bxl 8"), "line 1: `8` is not a literal operand (0-7)");
+        assert_eq!(error("out D"), "line 1: `D` is not a combo operand (0-3, A, B or C)");
+        assert_eq!(error("mul 3"), "line 1: unknown instruction `mul`");
+        assert_eq!(error("adv"), "line 1: `adv` needs an operand");
+        assert_eq!(error("out A B"), "line 1: unexpected `B` after the operand");
+        assert_eq!(error("jnz nowhere"), "line 1: unknown label `nowhere`");
+        assert_eq!(error("x:\nx: adv 1"), "line 2: label `x` is defined twice");
+        assert_eq!(error("1x: adv 1"), "line 1: `1x` is not a valid label");
+        assert_eq!(
+            error("adv 1\nadv 1\nadv 1\nadv 1\nend: jnz end"),
+            "line 5: label `end` is at address 8, out of reach of a 3-bit jump"
+        );
+    }
+
+    #[test]
+    fn test_round_trip_through_disassembler() {
+        let mut rng = Lcg(5);
+        for _ in 0..1_000 {
+            let mut program = Vec::new();
+            for _ in 0..1 + rng.next(9) {
+                let opcode = rng.next(8) as u8;
+                let operand = rng.next(8) as u8;
+                let combo = Instruction::try_from(opcode).unwrap().takes_combo();
+                program.extend([opcode, if combo && operand == 7 { 3 } else { operand }]);
+            }
+
+            let source = disassemble(&program).unwrap();
+            assert_eq!(assemble(&source), Ok(program), "{}", source);
+        }
+    }
+}
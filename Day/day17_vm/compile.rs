@@ -0,0 +1,190 @@
+//! Programs decoded once, for running the same program on many registers.
+//!
+//! `Vm` decodes `program[ip]` on every step. `Compiled` decodes an instruction
+//! at every address up front, odd ones included since a jump can land there,
+//! and resolves each combo operand to a register or a constant. Running it
+//! agrees with `Vm` under the default `InvalidOpcode::Halt`, without a step
+//! limit.
+
+use super::{Halt, Instruction, Output, Registers, Word};
+
+/// A combo operand, resolved.
+#[derive(Debug, Clone)]
+enum Operand<W> {
+    Value(W),
+    Register(usize),
+    /// Combo operand 7, which panics like `Vm::combo` if it is ever executed.
+    Reserved,
+}
+
+impl<W: Word> Operand<W> {
+    fn new(operand: u8) -> Self {
+        match operand {
+            0..=3 => Operand::Value(W::from_u8(operand)),
+            4..=6 => Operand::Register(operand as usize - 4),
+            _ => Operand::Reserved,
+        }
+    }
+
+    fn get<'r>(&'r self, registers: &'r Registers<W>) -> &'r W {
+        match self {
+            Operand::Value(value) => value,
+            Operand::Register(index) => &registers[*index],
+            Operand::Reserved => panic!("Invalid combo operand: 7"),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+enum Op<W> {
+    Adv(Operand<W>),
+    Bxl(W),
+    Bst(Operand<W>),
+    Jnz(usize),
+    Bxc,
+    Out(Operand<W>),
+    Bdv(Operand<W>),
+    Cdv(Operand<W>),
+    InvalidOpcode(u8),
+    /// An opcode in the last byte, with no operand after it.
+    End,
+}
+
+#[derive(Debug, Clone)]
+pub struct Compiled<W = usize> {
+    ops: Vec<Op<W>>,
+}
+
+impl<W: Word> Compiled<W> {
+    pub fn new(program: &[u8]) -> Self {
+        let ops = (0..program.len())
+            .map(|ip| {
+                let Some(&operand) = program.get(ip + 1) else {
+                    return Op::End;
+                };
+                match Instruction::try_from(program[ip]) {
+                    Ok(Instruction::Adv) => Op::Adv(Operand::new(operand)),
+                    Ok(Instruction::Bxl) => Op::Bxl(W::from_u8(operand)),
+                    Ok(Instruction::Bst) => Op::Bst(Operand::new(operand)),
+                    Ok(Instruction::Jnz) => Op::Jnz(operand as usize),
+                    Ok(Instruction::Bxc) => Op::Bxc,
+                    Ok(Instruction::Out) => Op::Out(Operand::new(operand)),
+                    Ok(Instruction::Bdv) => Op::Bdv(Operand::new(operand)),
+                    Ok(Instruction::Cdv) => Op::Cdv(Operand::new(operand)),
+                    Err(opcode) => Op::InvalidOpcode(opcode),
+                }
+            })
+            .collect();
+        Self { ops }
+    }
+
+    /// Runs from the start until the program halts, leaving the final values
+    /// in `registers`.
+    pub fn run(&self, registers: &mut Registers<W>, out: &mut impl Output) -> Halt {
+        let mut ip = 0;
+        while let Some(op) = self.ops.get(ip) {
+            ip += 2;
+            match op {
+                Op::Adv(operand) => registers[0] = registers[0].shr(operand.get(registers)),
+                Op::Bxl(value) => registers[1] = registers[1].xor(value),
+                Op::Bst(operand) => registers[1] = W::from_u8(operand.get(registers).low3()),
+                Op::Jnz(target) => {
+                    if !registers[0].is_zero() {
+                        ip = *target;
+                    }
+                }
+                Op::Bxc => registers[1] = registers[1].xor(&registers[2]),
+                Op::Out(operand) => {
+                    if !out.write(operand.get(registers).low3()) {
+                        return Halt::Sink;
+                    }
+                }
+                Op::Bdv(operand) => registers[1] = registers[0].shr(operand.get(registers)),
+                Op::Cdv(operand) => registers[2] = registers[0].shr(operand.get(registers)),
+                Op::InvalidOpcode(opcode) => return Halt::InvalidOpcode(*opcode),
+                Op::End => break,
+            }
+        }
+        Halt::End
+    }
+
+    /// Everything the program prints, like `super::run`.
+    pub fn output(&self, mut registers: Registers<W>) -> Vec<u8> {
+        let mut output = Vec::new();
+        self.run(&mut registers, &mut output);
+        output
+    }
+
+    /// Whether the program prints exactly `expected`, stopping at the first
+    /// value that differs. Same as comparing `output` to `expected`.
+    pub fn prints(&self, mut registers: Registers<W>, expected: &[u8]) -> bool {
+        let mut printed = 0;
+        let halt = self.run(&mut registers, &mut |value| {
+            printed += 1;
+            expected.get(printed - 1) == Some(&value)
+        });
+        halt != Halt::Sink && printed == expected.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::tests::{random_program, Lcg};
+    use super::super::{run, Vm};
+    use super::*;
+    use num_bigint::BigUint;
+
+    #[test]
+    fn test_matches_vm() {
+        let mut rng = Lcg(39);
+        for _ in 0..2_000 {
+            let program = random_program(&mut rng);
+            let registers = [rng.next(1 << 40) as usize, rng.next(1 << 20) as usize, rng.next(1 << 20) as usize];
+            let output = run(registers, &program);
+
+            assert_eq!(Compiled::new(&program).output(registers), output, "{:?}", program);
+            assert_eq!(Compiled::new(&program).output(registers.map(BigUint::from)), output);
+            assert!(Compiled::new(&program).prints(registers, &output));
+        }
+    }
+
+    #[test]
+    fn test_odd_jumps_and_invalid_opcodes() {
+        // `jnz 3` lands on the operand of `bxl 0`, which reads as `adv 1`.
+        let programs: [&[u8]; 4] = [
+            &[3, 3, 1, 0, 1, 5, 4, 3, 0],
+            &[5, 4, 9, 0, 5, 4],
+            &[0, 1, 5, 4, 3],
+            &[5, 4, 3, 200],
+        ];
+        for program in programs {
+            let mut expected = Vec::new();
+            let mut vm = Vm::new([11usize, 2, 3], program);
+            let halt = vm.run(&mut expected);
+
+            let mut output = Vec::new();
+            let mut registers = [11, 2, 3];
+            let compiled = Compiled::new(program);
+            assert_eq!(compiled.run(&mut registers, &mut output), halt, "{:?}", program);
+            assert_eq!((output, registers), (expected, vm.registers), "{:?}", program);
+        }
+    }
+
+    #[test]
+    fn test_prints() {
+        let compiled = Compiled::new(&[0, 3, 5, 4, 3, 0]);
+        assert!(compiled.prints([117_440usize, 0, 0], &[0, 3, 5, 4, 3, 0]));
+        assert!(!compiled.prints([117_440, 0, 0], &[0, 3, 5, 4, 3]));
+        assert!(!compiled.prints([117_440, 0, 0], &[0, 3, 5, 4, 3, 0, 0]));
+        assert!(!compiled.prints([117_441, 0, 0], &[1, 3, 5, 4, 3, 0]));
+        assert!(compiled.prints([0, 0, 0], &[0]));
+    }
+
+    #[test]
+    #[should_panic(expected = "Invalid combo operand: 7")]
+    fn test_reserved_operand_panics_when_run() {
+        let compiled = Compiled::new(&[3, 4, 5, 7]);
+        assert_eq!(compiled.output([1usize, 0, 0]), Vec::<u8>::new());
+        compiled.output([0, 0, 0]);
+    }
+}
@@ -0,0 +1,192 @@
+//! Interactive step debugger for the VM.
+//!
+//! Commands, one per line:
+//!
+//! | command              | effect                                         |
+//! |----------------------|------------------------------------------------|
+//! | `s`, `step [n]`      | run one (or `n`) instructions                  |
+//! | `c`, `continue`      | run until a breakpoint or the machine halts    |
+//! | `b`, `break <ip>`    | stop before the instruction at `ip`            |
+//! | `d`, `delete <ip>`   | remove the breakpoint at `ip`                  |
+//! | `r`, `regs`          | show the instruction pointer and registers     |
+//! | `set <reg> <value>`  | change register A, B, C or the `ip`            |
+//! | `l`, `list`          | disassemble, marking `ip` and breakpoints      |
+//! | `o`, `out`           | show the output so far                         |
+//! | `q`, `quit`          | leave the debugger                             |
+
+use super::disasm::listing;
+use super::{Halt, Vm, Word};
+use std::collections::BTreeSet;
+use std::io::{self, BufRead, Write};
+
+pub struct Debugger<'a, W = usize> {
+    vm: Vm<'a, W>,
+    breakpoints: BTreeSet<usize>,
+    output: Vec<u8>,
+    halted: Option<Halt>,
+}
+
+impl<'a, W: Word> Debugger<'a, W> {
+    pub fn new(vm: Vm<'a, W>) -> Self {
+        Self {
+            vm,
+            breakpoints: BTreeSet::new(),
+            output: Vec::new(),
+            halted: None,
+        }
+    }
+
+    /// Reads commands from `input` until `quit` or end of input.
+    pub fn repl(&mut self, input: impl BufRead, out: &mut impl Write) -> io::Result<()> {
+        write!(out, "(vm) ")?;
+        out.flush()?;
+        for line in input.lines() {
+            if !self.command(&line?, out)? {
+                break;
+            }
+            write!(out, "(vm) ")?;
+            out.flush()?;
+        }
+        writeln!(out)
+    }
+
+    /// Runs one command, returning `false` once the user asks to quit.
+    pub fn command(&mut self, line: &str, out: &mut impl Write) -> io::Result<bool> {
+        let words: Vec<&str> = line.split_whitespace().collect();
+        let number = |idx: usize| words.get(idx).and_then(|word| word.parse::<usize>().ok());
+
+        match words[..] {
+            [] => {}
+            ["q" | "quit"] => return Ok(false),
+            ["s" | "step"] => self.run(1, out)?,
+            ["s" | "step", _] => match number(1) {
+                Some(count) => self.run(count, out)?,
+                None => writeln!(out, "usage: step [count]")?,
+            },
+            ["c" | "continue"] => self.run(usize::MAX, out)?,
+            ["b" | "break", _] => match number(1) {
+                Some(ip) => {
+                    self.breakpoints.insert(ip);
+                    writeln!(out, "breakpoint at {}", ip)?;
+                }
+                None => writeln!(out, "usage: break <ip>")?,
+            },
+            ["d" | "delete", _] => match number(1) {
+                Some(ip) if self.breakpoints.remove(&ip) => writeln!(out, "removed breakpoint at {}", ip)?,
+                _ => writeln!(out, "no breakpoint at {}", words[1])?,
+            },
+            ["r" | "regs"] => self.show_registers(out)?,
+            ["set", register, value] => match self.set(register, value) {
+                Ok(()) => self.show_registers(out)?,
+                Err(message) => writeln!(out, "{}", message)?,
+            },
+            ["l" | "list"] => {
+                for (idx, line) in listing(self.vm.program()).lines().enumerate() {
+                    let ip = idx * 2;
+                    let marker = if ip == self.vm.ip { "=>" } else { "  " };
+                    let breakpoint = if self.breakpoints.contains(&ip) { "*" } else { " " };
+                    writeln!(out, "{}{}{}", marker, breakpoint, line)?;
+                }
+            }
+            ["o" | "out"] => writeln!(out, "{}", self.joined_output())?,
+            _ => writeln!(out, "unknown command `{}`", line.trim())?,
+        }
+
+        Ok(true)
+    }
+
+    fn set(&mut self, register: &str, value: &str) -> Result<(), String> {
+        let usage = || "usage: set <A|B|C|ip> <value>".to_string();
+        let index = match register.to_ascii_uppercase().as_str() {
+            "A" => 0,
+            "B" => 1,
+            "C" => 2,
+            "IP" => {
+                self.vm.ip = value.parse().map_err(|_| usage())?;
+                self.halted = None;
+                return Ok(());
+            }
+            _ => return Err(format!("unknown register `{}`", register)),
+        };
+        self.vm.registers[index] = value.parse().map_err(|_| usage())?;
+        Ok(())
+    }
+
+    /// Runs up to `count` instructions, stopping early at breakpoints other than
+    /// the one execution starts on.
+    fn run(&mut self, count: usize, out: &mut impl Write) -> io::Result<()> {
+        if let Some(halt) = self.halted {
+            return writeln!(out, "halted ({:?})", halt);
+        }
+
+        for done in 0..count {
+            if done > 0 && self.breakpoints.contains(&self.vm.ip) {
+                writeln!(out, "breakpoint at {}", self.vm.ip)?;
+                break;
+            }
+            if let Some(halt) = self.vm.step(&mut self.output) {
+                self.halted = Some(halt);
+                writeln!(out, "halted ({:?}), output: {}", halt, self.joined_output())?;
+                return Ok(());
+            }
+        }
+
+        self.show_registers(out)
+    }
+
+    fn show_registers(&self, out: &mut impl Write) -> io::Result<()> {
+        let [a, b, c] = &self.vm.registers;
+        writeln!(out, "ip={} A={} B={} C={}", self.vm.ip, a, b, c)
+    }
+
+    fn joined_output(&self) -> String {
+        self.output.iter().map(u8::to_string).collect::<Vec<_>>().join(",")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn session(commands: &str) -> String {
+        let program = [0, 1, 5, 4, 3, 0];
+        let mut debugger = Debugger::new(Vm::new([729usize, 0, 0], &program));
+        let mut out = Vec::new();
+        debugger.repl(commands.as_bytes(), &mut out).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn test_step_and_breakpoints() {
+        let transcript = session("step\nb 4\nc\nc\nregs\nout\n");
+        assert_eq!(
+            transcript,
+            "(vm) ip=2 A=364 B=0 C=0
+(vm) breakpoint at 4
+(vm) breakpoint at 4
+ip=4 A=364 B=0 C=0
+(vm) breakpoint at 4
+ip=4 A=182 B=0 C=0
+(vm) ip=4 A=182 B=0 C=0
+(vm) 4,6
+(vm) \n"
+        );
+    }
+
+    #[test]
+    fn test_edit_registers_and_finish() {
+        let transcript = session("set A 8\nset X 1\nlist\nd 4\ncontinue\nstep\nq\n");
+        assert_eq!(
+            transcript,
+            "(vm) ip=0 A=8 B=0 C=0
+(vm) unknown register `X`
+(vm) =>   0: 0,1  adv 1
+     2: 5,4  out A
+     4: 3,0  jnz 0
+(vm) no breakpoint at 4
+(vm) halted (End), output: 4,2,1,0
+(vm) halted (End)
+(vm) \n"
+        );
+    }
+}
@@ -0,0 +1,334 @@
+//! Readable views of day 17 programs: a mnemonic listing, and pseudo-code for
+//! the usual "print something about A, shift A, repeat until zero" loop.
+
+use super::Instruction;
+use std::fmt::{self, Write};
+
+/// One decoded instruction with its raw operand.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Op {
+    pub instruction: Instruction,
+    pub operand: u8,
+}
+
+/// How a combo operand reads in mnemonics: its literal value or register name.
+pub fn combo_name(operand: u8) -> String {
+    match operand {
+        4 => "A".to_string(),
+        5 => "B".to_string(),
+        6 => "C".to_string(),
+        _ => operand.to_string(),
+    }
+}
+
+impl fmt::Display for Op {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = self.instruction.mnemonic();
+        match self.instruction {
+            // `bxc` ignores its operand, so only show one that isn't the usual 0.
+            Instruction::Bxc if self.operand == 0 => write!(f, "{}", name),
+            instruction if instruction.takes_combo() => {
+                write!(f, "{} {}", name, combo_name(self.operand))
+            }
+            _ => write!(f, "{} {}", name, self.operand),
+        }
+    }
+}
+
+/// Decodes the whole program, or `None` if it has an invalid opcode or an
+/// opcode without an operand.
+pub fn decode(program: &[u8]) -> Option<Vec<Op>> {
+    if !program.len().is_multiple_of(2) {
+        return None;
+    }
+    program
+        .chunks(2)
+        .map(|pair| {
+            Instruction::try_from(pair[0]).ok().map(|instruction| Op {
+                instruction,
+                operand: pair[1],
+            })
+        })
+        .collect()
+}
+
+/// One line per instruction: its address, raw bytes and mnemonic.
+pub fn listing(program: &[u8]) -> String {
+    let mut text = String::new();
+    for (idx, pair) in program.chunks(2).enumerate() {
+        let ip = idx * 2;
+        let line = match (pair, Instruction::try_from(pair[0])) {
+            (&[_, operand], Ok(instruction)) => {
+                let op = Op { instruction, operand };
+                if instruction.takes_combo() && operand == 7 {
+                    format!("{}  ; invalid combo operand", op)
+                } else {
+                    op.to_string()
+                }
+            }
+            (&[opcode, _], Err(_)) => format!("???  ; invalid opcode {}", opcode),
+            _ => "???  ; missing operand".to_string(),
+        };
+        let bytes = pair.iter().map(u8::to_string).collect::<Vec<_>>().join(",");
+        writeln!(text, "{:>3}: {:<4} {}", ip, bytes, line).unwrap();
+    }
+    text
+}
+
+/// Source text for `asm::assemble`, one mnemonic per line. Jump targets that
+/// land on an instruction get a label, and the result assembles back to the
+/// same bytes. `None` if the program cannot be decoded.
+pub fn disassemble(program: &[u8]) -> Option<String> {
+    let ops = decode(program)?;
+    let is_target = |ip: usize| {
+        ip.is_multiple_of(2)
+            && ip < program.len()
+            && ops.iter().any(|op| op.instruction == Instruction::Jnz && op.operand as usize == ip)
+    };
+
+    let mut text = String::new();
+    for (idx, op) in ops.iter().enumerate() {
+        if is_target(idx * 2) {
+            writeln!(text, "L{}:", idx * 2).unwrap();
+        }
+        match op.instruction {
+            Instruction::Jnz if is_target(op.operand as usize) => {
+                writeln!(text, "    jnz L{}", op.operand).unwrap()
+            }
+            _ => writeln!(text, "    {}", op).unwrap(),
+        }
+    }
+    Some(text)
+}
+
+/// Value of a register partway through one loop iteration, in terms of the
+/// registers at the start of that iteration.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Expr {
+    Register(char),
+    Literal(usize),
+    Mod8(Box<Expr>),
+    Shr(Box<Expr>, Box<Expr>),
+    Xor(Vec<Expr>),
+}
+
+impl Expr {
+    fn mod8(self) -> Expr {
+        match self {
+            Expr::Literal(n) => Expr::Literal(n % 8),
+            Expr::Mod8(_) => self,
+            _ => Expr::Mod8(Box::new(self)),
+        }
+    }
+
+    fn shr(self, by: Expr) -> Expr {
+        match by {
+            Expr::Literal(0) => self,
+            _ => Expr::Shr(Box::new(self), Box::new(by)),
+        }
+    }
+
+    /// XOR of both sides, flattened so that literal terms fold into one.
+    fn xor(self, other: Expr) -> Expr {
+        let mut literal = 0;
+        let mut terms = Vec::new();
+        for term in [self, other] {
+            match term {
+                Expr::Xor(inner) => terms.extend(inner),
+                term => terms.push(term),
+            }
+        }
+        terms.retain(|term| match term {
+            Expr::Literal(n) => {
+                literal ^= n;
+                false
+            }
+            _ => true,
+        });
+        if literal != 0 {
+            terms.push(Expr::Literal(literal));
+        }
+
+        match terms.len() {
+            0 => Expr::Literal(0),
+            1 => terms.pop().unwrap(),
+            _ => Expr::Xor(terms),
+        }
+    }
+
+    fn is_atom(&self) -> bool {
+        matches!(self, Expr::Register(_) | Expr::Literal(_))
+    }
+}
+
+impl fmt::Display for Expr {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let part = |expr: &Expr| match expr.is_atom() {
+            true => expr.to_string(),
+            false => format!("({})", expr),
+        };
+        match self {
+            Expr::Register(name) => write!(f, "{}", name),
+            Expr::Literal(n) => write!(f, "{}", n),
+            Expr::Mod8(expr) => write!(f, "{} % 8", part(expr)),
+            Expr::Shr(expr, by) => write!(f, "{} >> {}", part(expr), part(by)),
+            Expr::Xor(terms) => {
+                write!(f, "{}", terms.iter().map(part).collect::<Vec<_>>().join(" ^ "))
+            }
+        }
+    }
+}
+
+/// Pseudo-code for one iteration of a program made of a single loop: a body
+/// with exactly one `adv` by a non-zero literal, no other jumps, and a final
+/// `jnz 0`. Also spells out what each `out` prints in terms of the registers
+/// at the start of the iteration.
+pub fn decompile(program: &[u8]) -> Result<String, String> {
+    let ops = decode(program).ok_or("program has an invalid opcode or a missing operand")?;
+    let (last, body) = ops.split_last().ok_or("program is empty")?;
+
+    if last.instruction != Instruction::Jnz || last.operand != 0 {
+        return Err(format!("program ends with `{}` instead of `jnz 0`", last));
+    }
+    if let Some(op) = body.iter().find(|op| op.instruction == Instruction::Jnz) {
+        return Err(format!("`{}` jumps from inside the loop body", op));
+    }
+    if let Some(op) = body.iter().find(|op| op.instruction.takes_combo() && op.operand == 7) {
+        return Err(format!("`{}` uses the reserved combo operand 7", op));
+    }
+    match body.iter().filter(|op| op.instruction == Instruction::Adv).collect::<Vec<_>>()[..] {
+        [op] if (1..=3).contains(&op.operand) => {}
+        [op] if op.operand == 0 => return Err(format!("`{}` never shrinks A, so the loop never ends", op)),
+        [op] => return Err(format!("`{}` does not shift A by a literal", op)),
+        _ => return Err("loop body must shift A with exactly one `adv`".to_string()),
+    }
+
+    let mut code = String::from("do {\n");
+    let mut prints = Vec::new();
+    let mut registers = [Expr::Register('A'), Expr::Register('B'), Expr::Register('C')];
+
+    for op in body {
+        let name = combo_name(op.operand);
+        let value = match op.operand {
+            4..=6 if op.instruction.takes_combo() => registers[op.operand as usize - 4].clone(),
+            _ => Expr::Literal(op.operand as usize),
+        };
+        let [a, b, c] = &registers;
+
+        let statement = match op.instruction {
+            Instruction::Adv => {
+                registers[0] = a.clone().shr(value);
+                format!("A = A >> {}", name)
+            }
+            Instruction::Bxl => {
+                registers[1] = b.clone().xor(Expr::Literal(op.operand as usize));
+                format!("B = B ^ {}", op.operand)
+            }
+            Instruction::Bst => {
+                registers[1] = value.mod8();
+                match op.operand {
+                    0..=3 => format!("B = {}", op.operand),
+                    _ => format!("B = {} % 8", name),
+                }
+            }
+            Instruction::Bxc => {
+                registers[1] = b.clone().xor(c.clone());
+                "B = B ^ C".to_string()
+            }
+            Instruction::Out => {
+                prints.push(value.mod8());
+                match op.operand {
+                    0..=3 => format!("out({})", op.operand),
+                    _ => format!("out({} % 8)", name),
+                }
+            }
+            Instruction::Bdv => {
+                registers[1] = a.clone().shr(value);
+                format!("B = A >> {}", name)
+            }
+            Instruction::Cdv => {
+                registers[2] = a.clone().shr(value);
+                format!("C = A >> {}", name)
+            }
+            Instruction::Jnz => unreachable!(),
+        };
+        writeln!(code, "    {}", statement).unwrap();
+    }
+    code.push_str("} while A != 0\n");
+
+    for expr in prints {
+        writeln!(code, "// each iteration prints {}", expr).unwrap();
+    }
+
+    Ok(code)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PROGRAM: [u8; 16] = [2, 4, 1, 7, 7, 5, 1, 7, 0, 3, 4, 1, 5, 5, 3, 0];
+
+    #[test]
+    fn test_listing() {
+        assert_eq!(
+            listing(&PROGRAM),
+            "  0: 2,4  bst A
+  2: 1,7  bxl 7
+  4: 7,5  cdv B
+  6: 1,7  bxl 7
+  8: 0,3  adv 3
+ 10: 4,1  bxc 1
+ 12: 5,5  out B
+ 14: 3,0  jnz 0
+"
+        );
+        assert_eq!(
+            listing(&[0, 7, 9, 1, 5]),
+            "  0: 0,7  adv 7  ; invalid combo operand
+  2: 9,1  ???  ; invalid opcode 9
+  4: 5    ???  ; missing operand
+"
+        );
+    }
+
+    #[test]
+    fn test_decompile() {
+        assert_eq!(
+            decompile(&PROGRAM).unwrap(),
+            "do {
+    B = A % 8
+    B = B ^ 7
+    C = A >> B
+    B = B ^ 7
+    A = A >> 3
+    B = B ^ C
+    out(B % 8)
+} while A != 0
+// each iteration prints ((A % 8) ^ (A >> ((A % 8) ^ 7))) % 8
+"
+        );
+        assert_eq!(
+            decompile(&[0, 1, 5, 4, 3, 0]).unwrap(),
+            "do {
+    A = A >> 1
+    out(A % 8)
+} while A != 0
+// each iteration prints (A >> 1) % 8
+"
+        );
+    }
+
+    #[test]
+    fn test_decompile_rejects_other_shapes() {
+        assert!(decompile(&[0, 3, 5, 4]).unwrap_err().contains("instead of `jnz 0`"));
+        assert!(decompile(&[0, 3, 3, 4, 3, 0]).unwrap_err().contains("`jnz 4` jumps"));
+        assert!(decompile(&[0, 4, 5, 4, 3, 0]).unwrap_err().contains("`adv A`"));
+        assert_eq!(
+            decompile(&[0, 0, 5, 4, 3, 0]),
+            Err("`adv 0` never shrinks A, so the loop never ends".to_string())
+        );
+        assert!(decompile(&[5, 4, 3, 0]).unwrap_err().contains("exactly one `adv`"));
+        assert!(decompile(&[0, 3, 5, 7, 3, 0]).unwrap_err().contains("reserved"));
+    }
+}
@@ -0,0 +1,369 @@
+//! Search for the smallest A that makes a program print itself.
+//!
+//! A is fixed one bit at a time from the most significant end. After each
+//! choice the program runs on an A whose remaining low bits are unknown: every
+//! value tracks which of its bits are known, and any output whose known bits
+//! disagree with the program rules the whole branch out. The run stops early,
+//! without ruling anything out, as soon as a jump depends on unknown bits.
+//!
+//! The only things assumed about the program are that it is valid, that every
+//! run halts within `STEP_LIMIT` steps, and that outputs pin down A's bits well
+//! enough to stay within `NODE_LIMIT` candidates. Programs that break either
+//! limit fail with an error rather than hanging.
+//!
+//! The search works in the caller's register type. A quine prints about three
+//! bits of A per output, so programs past 21 values need `u128` or `BigUint`.
+
+use super::{Instruction, Vm, Word};
+use std::fmt;
+
+/// Most instructions a single run may execute.
+pub const STEP_LIMIT: usize = 100_000;
+/// Most partial values of A the search may try.
+pub const NODE_LIMIT: usize = 1_000_000;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum QuineError {
+    InvalidProgram(String),
+    StepLimit,
+    NodeLimit,
+    NotFound,
+}
+
+impl fmt::Display for QuineError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            QuineError::InvalidProgram(reason) => write!(f, "invalid program: {}", reason),
+            QuineError::StepLimit => write!(
+                f,
+                "the program ran for more than {} steps, but the search needs every run to halt",
+                STEP_LIMIT
+            ),
+            QuineError::NodeLimit => write!(
+                f,
+                "gave up after {} candidates: too few outputs could be ruled out without knowing all of A",
+                NODE_LIMIT
+            ),
+            QuineError::NotFound => {
+                write!(f, "no A that fits in a register makes the program print itself")
+            }
+        }
+    }
+}
+
+impl std::error::Error for QuineError {}
+
+/// A register value of which only the bits outside `unknown` are determined.
+/// Unknown bits of `value` are zero.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Bits<W> {
+    value: W,
+    unknown: W,
+}
+
+/// `a & !b`, for words that cannot represent `!b`.
+fn and_not<W: Word>(a: &W, b: &W) -> W {
+    a.or(b).xor(b)
+}
+
+impl<W: Word> Bits<W> {
+    fn exact(value: W) -> Self {
+        Bits {
+            value,
+            unknown: W::from_u8(0),
+        }
+    }
+
+    fn exact_value(&self) -> Option<&W> {
+        self.unknown.is_zero().then_some(&self.value)
+    }
+
+    fn is_zero(&self) -> Option<bool> {
+        if !self.value.is_zero() {
+            Some(false)
+        } else if self.unknown.is_zero() {
+            Some(true)
+        } else {
+            None
+        }
+    }
+
+    fn xor(&self, other: &Bits<W>) -> Bits<W> {
+        let unknown = self.unknown.or(&other.unknown);
+        Bits {
+            value: and_not(&self.value.xor(&other.value), &unknown),
+            unknown,
+        }
+    }
+
+    fn low3(&self) -> Bits<W> {
+        Bits {
+            value: W::from_u8(self.value.low3()),
+            unknown: W::from_u8(self.unknown.low3()),
+        }
+    }
+
+    /// The bits both values agree on.
+    fn join(&self, other: &Bits<W>) -> Bits<W> {
+        let unknown = self.unknown.or(&other.unknown).or(&self.value.xor(&other.value));
+        Bits {
+            value: and_not(&self.value, &unknown),
+            unknown,
+        }
+    }
+
+    fn shr(&self, by: &Bits<W>) -> Bits<W> {
+        match by.exact_value() {
+            Some(by) => Bits {
+                value: self.value.shr(by),
+                unknown: self.unknown.shr(by),
+            },
+            // Try every shift the known bits allow, if there are only a few.
+            None if by.unknown.count_ones() <= 3 => (0..1u8 << by.unknown.count_ones())
+                .map(|pick| spread_bits(pick, &by.unknown).or(&by.value))
+                .map(|by| self.shr(&Bits::exact(by)))
+                .reduce(|a, b| a.join(&b))
+                .unwrap(),
+            // A shift by an unknown amount can move any set bit down to any
+            // lower position, so nothing below the highest possible bit is known.
+            None => Bits {
+                value: W::from_u8(0),
+                unknown: self.value.or(&self.unknown).fill_below(),
+            },
+        }
+    }
+}
+
+/// Deposits the low bits of `pick` into the set bits of `mask`, lowest first.
+fn spread_bits<W: Word>(mut pick: u8, mask: &W) -> W {
+    let mut mask = mask.clone();
+    let mut result = W::from_u8(0);
+    while let Some(index) = mask.lowest_bit() {
+        let bit = W::bit(index).unwrap();
+        if pick & 1 != 0 {
+            result = result.or(&bit);
+        }
+        pick >>= 1;
+        mask = mask.xor(&bit);
+    }
+    result
+}
+
+/// Runs the program on a partially known A and reports whether it could still
+/// print itself.
+fn could_be_quine<W: Word>(program: &[u8], registers: [Bits<W>; 3]) -> Result<bool, QuineError> {
+    let mut registers = registers;
+    let mut ip = 0;
+    let mut printed = 0;
+
+    for _ in 0..STEP_LIMIT {
+        if ip + 1 >= program.len() {
+            return Ok(printed == program.len());
+        }
+
+        let instruction = Instruction::try_from(program[ip]).unwrap();
+        let operand = program[ip + 1];
+        let combo = match operand {
+            4..=6 if instruction.takes_combo() => registers[operand as usize - 4].clone(),
+            _ => Bits::exact(W::from_u8(operand)),
+        };
+        ip += 2;
+
+        match instruction {
+            Instruction::Adv => registers[0] = registers[0].shr(&combo),
+            Instruction::Bxl => registers[1] = registers[1].xor(&combo),
+            Instruction::Bst => registers[1] = combo.low3(),
+            Instruction::Jnz => match registers[0].is_zero() {
+                Some(true) => {}
+                Some(false) => ip = operand as usize,
+                None => return Ok(true),
+            },
+            Instruction::Bxc => registers[1] = registers[1].xor(&registers[2]),
+            Instruction::Out => {
+                let value = combo.low3();
+                let expected = match program.get(printed) {
+                    Some(&expected) => expected,
+                    None => return Ok(false),
+                };
+                if (value.value.low3() ^ expected) & !value.unknown.low3() != 0 {
+                    return Ok(false);
+                }
+                printed += 1;
+            }
+            Instruction::Bdv => registers[1] = registers[0].shr(&combo),
+            Instruction::Cdv => registers[2] = registers[0].shr(&combo),
+        }
+    }
+
+    Err(QuineError::StepLimit)
+}
+
+fn validate(program: &[u8]) -> Result<(), QuineError> {
+    if !program.len().is_multiple_of(2) {
+        return Err(QuineError::InvalidProgram("an opcode has no operand".to_string()));
+    }
+    for (idx, pair) in program.chunks(2).enumerate() {
+        let instruction = Instruction::try_from(pair[0]).map_err(|opcode| {
+            QuineError::InvalidProgram(format!("invalid opcode {} at {}", opcode, idx * 2))
+        })?;
+        if instruction.takes_combo() && pair[1] == 7 {
+            return Err(QuineError::InvalidProgram(format!(
+                "reserved combo operand 7 at {}",
+                idx * 2 + 1
+            )));
+        }
+    }
+    Ok(())
+}
+
+/// Smallest positive A that makes `program` print itself, with B and C as given.
+pub fn find_quine<W: Word>(program: &[u8], b: W, c: W) -> Result<W, QuineError> {
+    validate(program)?;
+
+    let mut nodes = 0;
+    let fixed = [Bits::exact(b), Bits::exact(c)];
+    let mut below = W::from_u8(0);
+    for top in (0..).take_while(|&top| W::BITS.is_none_or(|bits| top < bits)) {
+        // Every A with `top` as its highest set bit, smallest first.
+        let value = W::bit(top).unwrap();
+        let a = Bits {
+            value: value.clone(),
+            unknown: below.clone(),
+        };
+        if let Some(a) = search(program, a, top, &fixed, &mut nodes)? {
+            return Ok(a);
+        }
+        below = below.or(&value);
+    }
+
+    Err(QuineError::NotFound)
+}
+
+/// Depth-first search over the `bit` lowest unknown bits of `a`, zero first.
+fn search<W: Word>(
+    program: &[u8],
+    a: Bits<W>,
+    bit: u32,
+    fixed: &[Bits<W>; 2],
+    nodes: &mut usize,
+) -> Result<Option<W>, QuineError> {
+    let [b, c] = fixed;
+    *nodes += 1;
+    if *nodes > NODE_LIMIT {
+        return Err(QuineError::NodeLimit);
+    }
+    if !could_be_quine(program, [a.clone(), b.clone(), c.clone()])? {
+        return Ok(None);
+    }
+    if let Some(a) = a.exact_value() {
+        // Fully known runs are exact, but confirm with the real machine anyway.
+        let mut output = Vec::new();
+        Vm::new([a.clone(), b.value.clone(), c.value.clone()], program).run(&mut output);
+        return Ok((output == program).then(|| a.clone()));
+    }
+
+    let bit = bit - 1;
+    let mask = W::bit(bit).unwrap();
+    for value in [a.value.clone(), a.value.or(&mask)] {
+        let next = Bits {
+            value,
+            unknown: a.unknown.xor(&mask),
+        };
+        if let Some(a) = search(program, next, bit, fixed, nodes)? {
+            return Ok(Some(a));
+        }
+    }
+
+    Ok(None)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::run;
+    use super::*;
+    use num_bigint::BigUint;
+
+    #[test]
+    fn test_examples() {
+        assert_eq!(find_quine(&[0, 3, 5, 4, 3, 0], 0, 0), Ok(117_440usize));
+
+        let program = [2, 4, 1, 7, 7, 5, 1, 7, 0, 3, 4, 1, 5, 5, 3, 0];
+        assert_eq!(find_quine(&program, 0, 0), Ok(265_601_188_299_675usize));
+    }
+
+    #[test]
+    fn test_other_shapes() {
+        // Two bits per output: `adv 2`, `bst A`, `out B`, `jnz 0`.
+        let program = [0, 2, 2, 4, 5, 5, 3, 0];
+        let expected = (1usize..).find(|&a| run([a, 0, 0], &program) == program);
+        assert_eq!(find_quine(&program, 0, 0).ok(), expected);
+
+        // B carries over between iterations, so each output depends on the
+        // previous iteration's bits. Checked against a brute-force search.
+        let program = [0, 3, 5, 5, 6, 2, 3, 0];
+        assert_eq!(find_quine(&program, 0, 0), Ok(3_517_792usize));
+        assert_eq!(run([3_517_792usize, 0, 0], &program), program);
+
+        let program = [0, 2, 4, 3, 2, 4, 5, 5, 3, 0];
+        assert_eq!(find_quine(&program, 0, 0), Ok(871_200usize));
+    }
+
+    #[test]
+    fn test_long_programs() {
+        // `adv 3`, then `bxl 0` padding, then `out A` and `jnz 0`: the quine is
+        // the program itself read as base-8 digits, shifted up one digit.
+        let padded = |len: usize| {
+            let mut program = vec![0, 3];
+            while program.len() < len - 4 {
+                program.extend([1, 0]);
+            }
+            program.extend([5, 4, 3, 0]);
+            program
+        };
+        let expected = |program: &[u8]| {
+            program.iter().rev().fold(BigUint::default(), |a, &digit| (a + digit) << 3u32)
+        };
+
+        let program = padded(30);
+        assert_eq!(find_quine(&program, 0usize, 0), Err(QuineError::NotFound));
+        let a = find_quine(&program, 0u128, 0).unwrap();
+        assert_eq!(BigUint::from(a), expected(&program));
+        assert_eq!(run([a, 0, 0], &program), program);
+
+        let program = padded(60);
+        assert_eq!(find_quine(&program, 0u128, 0), Err(QuineError::NotFound));
+        let a = find_quine(&program, BigUint::default(), BigUint::default()).unwrap();
+        assert_eq!(a, expected(&program));
+
+        // The example with B and C in a wide type gives the same answer.
+        let program = [2, 4, 1, 7, 7, 5, 1, 7, 0, 3, 4, 1, 5, 5, 3, 0];
+        assert_eq!(find_quine(&program, BigUint::default(), BigUint::default()), Ok(265_601_188_299_675u64.into()));
+    }
+
+    #[test]
+    fn test_unknown_shifts() {
+        // `bdv 0`, `cdv 0`, `bxc`, `bxl 1` leave B = 1, so `adv B` shifts A by
+        // one, but the search sees a shift by A's unknown low bits. Those can
+        // move A's high bits anywhere below them.
+        let program = [6, 0, 7, 0, 4, 0, 1, 1, 0, 5, 0, 3, 0, 3, 5, 4, 3, 0];
+        let a: u128 = 2_014_814_956_003_534_150_118_349_639_665_058_560;
+        assert_eq!(run([a, 0, 0], &program), program);
+
+        let unknown = 0b1111_0000;
+        let partial = Bits { value: a & !unknown, unknown };
+        assert_eq!(could_be_quine(&program, [partial, Bits::exact(0), Bits::exact(0)]), Ok(true));
+
+        // The search doesn't know that B ^ C is zero when both are copies of
+        // A, so no output narrows A down. Giving up is fine; ruling the real
+        // answer out is not.
+        assert_eq!(find_quine(&program, 0u128, 0), Err(QuineError::NodeLimit));
+    }
+
+    #[test]
+    fn test_errors() {
+        assert_eq!(find_quine(&[1, 1, 3, 0], 0usize, 0), Err(QuineError::StepLimit));
+        assert!(matches!(find_quine(&[5, 7, 3, 0], 0usize, 0), Err(QuineError::InvalidProgram(_))));
+        assert!(matches!(find_quine(&[8, 0], 0usize, 0), Err(QuineError::InvalidProgram(_))));
+        assert_eq!(find_quine(&[0, 3, 3, 0], 0usize, 0), Err(QuineError::NotFound));
+    }
+}
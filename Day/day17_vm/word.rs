@@ -0,0 +1,161 @@
+//! Register types for the VM: fixed-width unsigned integers, or `BigUint` for
+//! values that no fixed width holds.
+
+use num_bigint::BigUint;
+use std::fmt;
+use std::str::FromStr;
+
+/// The operations the machine and the quine search need from a register.
+pub trait Word: Clone + PartialEq + fmt::Debug + fmt::Display + FromStr {
+    /// Width in bits, or `None` if values can grow without bound.
+    const BITS: Option<u32>;
+
+    fn from_u8(value: u8) -> Self;
+    /// `1 << index`, or `None` if that does not fit.
+    fn bit(index: u32) -> Option<Self>;
+    fn is_zero(&self) -> bool;
+    /// The value modulo 8.
+    fn low3(&self) -> u8;
+    fn or(&self, other: &Self) -> Self;
+    fn xor(&self, other: &Self) -> Self;
+    /// `self / 2^shift`, which is zero once the shift reaches the width.
+    fn shr(&self, shift: &Self) -> Self;
+    fn count_ones(&self) -> u64;
+    /// Index of the lowest set bit, or `None` for zero.
+    fn lowest_bit(&self) -> Option<u32>;
+    /// Every bit up to and including the highest set bit.
+    fn fill_below(&self) -> Self;
+}
+
+macro_rules! impl_word {
+    ($($ty:ty),*) => {$(
+        impl Word for $ty {
+            const BITS: Option<u32> = Some(<$ty>::BITS);
+
+            fn from_u8(value: u8) -> Self {
+                value as $ty
+            }
+
+            fn bit(index: u32) -> Option<Self> {
+                (1 as $ty).checked_shl(index)
+            }
+
+            fn is_zero(&self) -> bool {
+                *self == 0
+            }
+
+            fn low3(&self) -> u8 {
+                (*self % 8) as u8
+            }
+
+            fn or(&self, other: &Self) -> Self {
+                self | other
+            }
+
+            fn xor(&self, other: &Self) -> Self {
+                self ^ other
+            }
+
+            fn shr(&self, shift: &Self) -> Self {
+                u32::try_from(*shift)
+                    .ok()
+                    .and_then(|shift| self.checked_shr(shift))
+                    .unwrap_or(0)
+            }
+
+            fn count_ones(&self) -> u64 {
+                <$ty>::count_ones(*self) as u64
+            }
+
+            fn lowest_bit(&self) -> Option<u32> {
+                (*self != 0).then(|| self.trailing_zeros())
+            }
+
+            fn fill_below(&self) -> Self {
+                <$ty>::MAX.checked_shr(self.leading_zeros()).unwrap_or(0)
+            }
+        }
+    )*};
+}
+
+impl_word!(usize, u64, u128);
+
+impl Word for BigUint {
+    const BITS: Option<u32> = None;
+
+    fn from_u8(value: u8) -> Self {
+        BigUint::from(value)
+    }
+
+    fn bit(index: u32) -> Option<Self> {
+        Some(BigUint::from(1u8) << index)
+    }
+
+    fn is_zero(&self) -> bool {
+        self.bits() == 0
+    }
+
+    fn low3(&self) -> u8 {
+        self.iter_u32_digits().next().unwrap_or(0) as u8 % 8
+    }
+
+    fn or(&self, other: &Self) -> Self {
+        self | other
+    }
+
+    fn xor(&self, other: &Self) -> Self {
+        self ^ other
+    }
+
+    fn shr(&self, shift: &Self) -> Self {
+        match u64::try_from(shift) {
+            Ok(shift) if shift < self.bits() => self >> shift,
+            _ => BigUint::default(),
+        }
+    }
+
+    fn count_ones(&self) -> u64 {
+        BigUint::count_ones(self)
+    }
+
+    fn lowest_bit(&self) -> Option<u32> {
+        self.trailing_zeros().map(|index| index as u32)
+    }
+
+    fn fill_below(&self) -> Self {
+        (BigUint::from(1u8) << self.bits()) - 1u8
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn check<W: Word>(value: u128) {
+        let word = |value: u128| value.to_string().parse::<W>().ok().unwrap();
+        let (a, b) = (word(value), word(0b1011_0110));
+
+        assert_eq!(a.low3(), (value % 8) as u8);
+        assert_eq!(a.xor(&b), word(value ^ 0b1011_0110));
+        assert_eq!(a.or(&b), word(value | 0b1011_0110));
+        assert_eq!(a.shr(&W::from_u8(5)), word(value >> 5));
+        assert_eq!(a.shr(&word(1 << 40)), W::from_u8(0));
+        assert_eq!(a.count_ones(), value.count_ones() as u64);
+        assert_eq!(a.lowest_bit(), (value != 0).then(|| value.trailing_zeros()));
+        assert_eq!(W::bit(7), Some(W::from_u8(128)));
+        let filled = if value == 0 { 0 } else { u128::MAX >> value.leading_zeros() };
+        assert_eq!(a.fill_below(), word(filled));
+    }
+
+    #[test]
+    fn test_words_agree() {
+        for value in [0, 1, 0b1011_0111, u32::MAX as u128, u64::MAX as u128 - 6] {
+            check::<u64>(value);
+            check::<u128>(value);
+            check::<BigUint>(value);
+        }
+        check::<u128>(u128::MAX - 1);
+        check::<BigUint>(u128::MAX - 1);
+        assert_eq!(u64::bit(64), None);
+    }
+}
@@ -0,0 +1,451 @@
+//! Xorshift-style generators like the secret numbers of day 22: a word of
+//! `bits` bits, mixed by xoring in shifted copies of itself. Every such step is
+//! an invertible linear map over GF(2), which gives jumps, steps backwards and
+//! the lengths of the generator's cycles.
+
+/// Xor in the word shifted by this many bits, then drop what falls outside.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Shift {
+    Left(u32),
+    Right(u32),
+}
+
+impl Shift {
+    fn apply(self, seed: u64) -> u64 {
+        match self {
+            Shift::Left(by) => seed.checked_shl(by).unwrap_or(0),
+            Shift::Right(by) => seed.checked_shr(by).unwrap_or(0),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Xorshift {
+    bits: u32,
+    shifts: Vec<Shift>,
+}
+
+impl Xorshift {
+    /// Panics unless `bits` is in 1..=64 and no shift is by zero, which would
+    /// clear the word instead of mixing it.
+    pub fn new(bits: u32, shifts: &[Shift]) -> Self {
+        assert!((1..=64).contains(&bits), "width must be 1 to 64 bits, not {}", bits);
+        assert!(
+            !shifts.contains(&Shift::Left(0)) && !shifts.contains(&Shift::Right(0)),
+            "shifting by zero clears the word"
+        );
+        Self { bits, shifts: shifts.to_vec() }
+    }
+
+    /// The day 22 generator: `<< 6`, `>> 5` and `<< 11` on 24 bits.
+    pub fn day22() -> Self {
+        Self::new(24, &[Shift::Left(6), Shift::Right(5), Shift::Left(11)])
+    }
+
+    pub fn bits(&self) -> u32 {
+        self.bits
+    }
+
+    pub fn mask(&self) -> u64 {
+        u64::MAX >> (64 - self.bits)
+    }
+
+    pub fn step(&self, seed: u64) -> u64 {
+        let mask = self.mask();
+        self.shifts
+            .iter()
+            .fold(seed & mask, |seed, shift| (seed ^ shift.apply(seed)) & mask)
+    }
+
+    /// The seed that `step` turns into `seed`. Each shift S is undone by
+    /// 1 + S + S^2 + ..., which ends once the shifts push every bit out.
+    pub fn step_back(&self, seed: u64) -> u64 {
+        let mask = self.mask();
+        self.shifts.iter().rev().fold(seed & mask, |seed, shift| {
+            let mut term = seed;
+            let mut result = seed;
+            while term != 0 {
+                term = shift.apply(term) & mask;
+                result ^= term;
+            }
+            result
+        })
+    }
+
+    pub fn matrix(&self) -> Gf2Matrix {
+        Gf2Matrix::of(self.bits, |seed| self.step(seed))
+    }
+
+    /// The seed `n` steps after `seed`, in O(log n) matrix products.
+    pub fn jump(&self, seed: u64, n: u64) -> u64 {
+        self.matrix().pow(n).apply(seed)
+    }
+
+    /// The seed `n` steps before `seed`.
+    pub fn jump_back(&self, seed: u64, n: u64) -> u64 {
+        Gf2Matrix::of(self.bits, |seed| self.step_back(seed)).pow(n).apply(seed)
+    }
+
+    /// How many steps it takes `seed` to come back to itself.
+    pub fn cycle_length(&self, seed: u64) -> u128 {
+        order(self.matrix().annihilator(seed & self.mask()))
+    }
+
+    /// How many steps it takes every seed to come back to itself: the least
+    /// common multiple of all the cycle lengths.
+    pub fn period(&self) -> u128 {
+        order(self.matrix().minimal_polynomial())
+    }
+
+    /// Whether all the nonzero seeds lie on one cycle.
+    pub fn is_full_period(&self) -> bool {
+        self.period() == self.mask() as u128
+    }
+}
+
+/// A linear map on GF(2)^bits, stored as the image of each bit.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Gf2Matrix(Vec<u64>);
+
+impl Gf2Matrix {
+    /// The matrix of a linear map, read off from where it sends each bit.
+    pub fn of(bits: u32, map: impl Fn(u64) -> u64) -> Self {
+        Gf2Matrix((0..bits).map(|bit| map(1 << bit)).collect())
+    }
+
+    pub fn identity(bits: u32) -> Self {
+        Self::of(bits, |seed| seed)
+    }
+
+    pub fn apply(&self, seed: u64) -> u64 {
+        self.0
+            .iter()
+            .enumerate()
+            .filter(|&(bit, _)| seed >> bit & 1 == 1)
+            .fold(0, |result, (_, image)| result ^ image)
+    }
+
+    /// The map that applies `other` and then `self`.
+    pub fn after(&self, other: &Self) -> Self {
+        Gf2Matrix(other.0.iter().map(|&image| self.apply(image)).collect())
+    }
+
+    pub fn pow(&self, mut n: u64) -> Self {
+        let mut result = Self::identity(self.0.len() as u32);
+        let mut square = self.clone();
+        while n > 0 {
+            if n & 1 == 1 {
+                result = result.after(&square);
+            }
+            square = square.after(&square);
+            n >>= 1;
+        }
+        result
+    }
+
+    /// The lowest degree monic polynomial p with p(M) v = 0, found by reducing
+    /// v, Mv, M^2 v, ... until one depends on the ones before it.
+    fn annihilator(&self, seed: u64) -> u128 {
+        // rows[pivot] holds a reduced vector with that highest bit, and the
+        // polynomial in M that produced it from `seed`.
+        let mut rows = vec![None; self.0.len()];
+        let mut vector = seed;
+        let mut poly = 1;
+
+        loop {
+            let (mut reduced, mut combination) = (vector, poly);
+            for pivot in (0..rows.len()).rev() {
+                if let (true, Some((row, row_poly))) = (reduced >> pivot & 1 == 1, rows[pivot]) {
+                    reduced ^= row;
+                    combination ^= row_poly;
+                }
+            }
+            if reduced == 0 {
+                return combination;
+            }
+            rows[63 - reduced.leading_zeros() as usize] = Some((reduced, combination));
+            vector = self.apply(vector);
+            poly <<= 1;
+        }
+    }
+
+    /// The lowest degree monic polynomial p with p(M) = 0.
+    fn minimal_polynomial(&self) -> u128 {
+        (0..self.0.len()).fold(1, |poly, bit| lcm(poly, self.annihilator(1 << bit)))
+    }
+}
+
+// Polynomials over GF(2) with degree at most 64, bit i holding the coefficient
+// of x^i. Products of two reduced polynomials still fit in 128 bits.
+
+const X: u128 = 0b10;
+
+fn degree(poly: u128) -> u32 {
+    127 - poly.leading_zeros()
+}
+
+fn clmul(a: u128, b: u128) -> u128 {
+    (0..128).filter(|bit| b >> bit & 1 == 1).fold(0, |product, bit| product ^ a << bit)
+}
+
+fn div_rem(mut a: u128, b: u128) -> (u128, u128) {
+    let mut quotient = 0;
+    while a != 0 && degree(a) >= degree(b) {
+        let shift = degree(a) - degree(b);
+        quotient ^= 1 << shift;
+        a ^= b << shift;
+    }
+    (quotient, a)
+}
+
+fn gcd(a: u128, b: u128) -> u128 {
+    if b == 0 { a } else { gcd(b, div_rem(a, b).1) }
+}
+
+fn lcm(a: u128, b: u128) -> u128 {
+    clmul(a, div_rem(b, gcd(a, b)).0)
+}
+
+fn mul_mod(a: u128, b: u128, modulus: u128) -> u128 {
+    div_rem(clmul(a, b), modulus).1
+}
+
+fn pow_mod(mut base: u128, mut n: u128, modulus: u128) -> u128 {
+    let mut result = div_rem(1, modulus).1;
+    while n > 0 {
+        if n & 1 == 1 {
+            result = mul_mod(result, base, modulus);
+        }
+        base = mul_mod(base, base, modulus);
+        n >>= 1;
+    }
+    result
+}
+
+/// The least n > 0 with x^n = 1 modulo `modulus`, which must not be divisible
+/// by x.
+///
+/// Each irreducible factor f of degree d has x^(2^d - 1) = 1 mod f, and a
+/// factor repeated e times needs another power of two of at least e on top.
+/// That gives a multiple of the order, which then loses every prime factor it
+/// can spare.
+fn order(modulus: u128) -> u128 {
+    let mut rest = modulus;
+    let mut degrees = Vec::new();
+    let mut repeats: u128 = 1;
+    let mut frobenius = div_rem(X, modulus).1;
+
+    // The factors of degree d are the ones x^(2^d) - x shares with what is
+    // left once the smaller degrees are gone.
+    for d in 1.. {
+        if degree(rest) == 0 {
+            break;
+        }
+        frobenius = mul_mod(frobenius, frobenius, modulus);
+
+        let mut count = 0;
+        loop {
+            let common = gcd(rest, div_rem(frobenius ^ X, rest).1);
+            if degree(common) == 0 {
+                break;
+            }
+            rest = div_rem(rest, common).0;
+            count += 1;
+        }
+        if count > 0 {
+            degrees.push(d);
+            repeats = repeats.max(count);
+        }
+    }
+
+    let mut multiple: u128 = repeats.next_power_of_two();
+    let mut primes = if repeats > 1 { vec![2] } else { vec![] };
+    for d in degrees {
+        let mersenne = (1u128 << d) - 1;
+        multiple = multiple / integer_gcd(multiple, mersenne) * mersenne;
+        primes.extend(prime_factors(mersenne as u64));
+    }
+
+    for prime in primes {
+        while multiple.is_multiple_of(prime) && pow_mod(X, multiple / prime, modulus) == 1 {
+            multiple /= prime;
+        }
+    }
+    multiple
+}
+
+fn integer_gcd(a: u128, b: u128) -> u128 {
+    if b == 0 { a } else { integer_gcd(b, a % b) }
+}
+
+fn mul_mod_u64(a: u64, b: u64, modulus: u64) -> u64 {
+    (a as u128 * b as u128 % modulus as u128) as u64
+}
+
+fn pow_mod_u64(mut base: u64, mut n: u64, modulus: u64) -> u64 {
+    let mut result = 1 % modulus;
+    while n > 0 {
+        if n & 1 == 1 {
+            result = mul_mod_u64(result, base, modulus);
+        }
+        base = mul_mod_u64(base, base, modulus);
+        n >>= 1;
+    }
+    result
+}
+
+// Miller-Rabin, exact for every u64 with these bases.
+fn is_prime(n: u64) -> bool {
+    const BASES: [u64; 12] = [2, 3, 5, 7, 11, 13, 17, 19, 23, 29, 31, 37];
+    if n < 2 {
+        return false;
+    }
+    if let Some(&base) = BASES.iter().find(|&&base| n.is_multiple_of(base)) {
+        return n == base;
+    }
+
+    let odd = (n - 1) >> (n - 1).trailing_zeros();
+    BASES.iter().all(|&base| {
+        let mut x = pow_mod_u64(base, odd, n);
+        let mut d = odd;
+        while d != n - 1 && x != 1 && x != n - 1 {
+            x = mul_mod_u64(x, x, n);
+            d <<= 1;
+        }
+        x == n - 1 || d == odd
+    })
+}
+
+// Pollard's rho: some factor of the odd composite `n` other than 1 and `n`.
+fn split(n: u64) -> u64 {
+    for c in 1.. {
+        let next = |x: u64| (mul_mod_u64(x, x, n) + c) % n;
+        let (mut slow, mut fast, mut factor) = (2, 2, 1);
+        while factor == 1 {
+            slow = next(slow);
+            fast = next(next(fast));
+            factor = integer_gcd(slow.abs_diff(fast) as u128, n as u128) as u64;
+        }
+        if factor != n {
+            return factor;
+        }
+    }
+    unreachable!()
+}
+
+/// The distinct prime factors of `n`, in no particular order.
+fn prime_factors(n: u64) -> Vec<u128> {
+    let mut primes = Vec::new();
+    let mut stack = vec![n];
+    while let Some(mut n) = stack.pop() {
+        if n % 2 == 0 {
+            primes.push(2);
+            n >>= n.trailing_zeros();
+        }
+        if n == 1 {
+            continue;
+        }
+        if is_prime(n) {
+            primes.push(n as u128);
+        } else {
+            let factor = split(n);
+            stack.extend([factor, n / factor]);
+        }
+    }
+    primes.sort_unstable();
+    primes.dedup();
+    primes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashSet;
+
+    // Cycle lengths by walking every cycle, for small widths.
+    fn walk(generator: &Xorshift) -> Vec<u128> {
+        let mut lengths = vec![0; 1 << generator.bits()];
+        for seed in 0..lengths.len() {
+            if lengths[seed] == 0 {
+                let mut cycle = vec![seed as u64];
+                while generator.step(*cycle.last().unwrap()) != seed as u64 {
+                    cycle.push(generator.step(*cycle.last().unwrap()));
+                }
+                for &member in &cycle {
+                    lengths[member as usize] = cycle.len() as u128;
+                }
+            }
+        }
+        lengths
+    }
+
+    #[test]
+    fn test_day22() {
+        let generator = Xorshift::day22();
+        assert_eq!(generator.step(123), 15887950);
+        assert_eq!(generator.step_back(15887950), 123);
+        assert_eq!(generator.jump(123, 3), 527345);
+        assert_eq!(generator.jump_back(527345, 3), 123);
+
+        assert_eq!(generator.period(), (1 << 24) - 1);
+        assert!(generator.is_full_period());
+        assert_eq!(generator.cycle_length(123), (1 << 24) - 1);
+        assert_eq!(generator.cycle_length(0), 1);
+    }
+
+    #[test]
+    fn test_small_generators_against_walking() {
+        use Shift::*;
+        let families: [&[Shift]; 6] = [
+            &[Left(1)],
+            &[Right(1), Left(2)],
+            &[Left(3), Right(1), Left(1)],
+            &[Left(6), Right(5), Left(11)],
+            &[Left(2), Left(2)],
+            &[Right(2), Left(5), Right(3)],
+        ];
+        for shifts in families {
+            for bits in 1..=11 {
+                let generator = Xorshift::new(bits, shifts);
+                let lengths = walk(&generator);
+
+                for (seed, &length) in lengths.iter().enumerate() {
+                    assert_eq!(generator.cycle_length(seed as u64), length, "{:?} {} bits, seed {}", shifts, bits, seed);
+                    assert_eq!(generator.step_back(generator.step(seed as u64)), seed as u64);
+                }
+                let distinct: HashSet<_> = lengths.into_iter().collect();
+                let lcm = distinct.into_iter().fold(1, |lcm, length| lcm / integer_gcd(lcm, length) * length);
+                assert_eq!(generator.period(), lcm, "{:?} {} bits", shifts, bits);
+            }
+        }
+    }
+
+    #[test]
+    fn test_wide_generators() {
+        use Shift::*;
+        let xorshift32 = Xorshift::new(32, &[Left(13), Right(17), Left(5)]);
+        assert!(xorshift32.is_full_period());
+        assert_eq!(xorshift32.step(1), 270369);
+
+        let xorshift64 = Xorshift::new(64, &[Left(13), Right(7), Left(17)]);
+        assert_eq!(xorshift64.period(), u64::MAX as u128);
+        let seed = 0x9E37_79B9_7F4A_7C15;
+        assert_eq!(xorshift64.jump_back(xorshift64.jump(seed, 1 << 50), 1 << 50), seed);
+        assert_eq!(xorshift64.jump(seed, u64::MAX), seed);
+
+        // Over GF(2), (1 + S)^2 = 1 + S^2: shifting by 13 twice is shifting by
+        // 26 once, and after four steps that would be a shift by 104.
+        let doubled = Xorshift::new(64, &[Left(13), Left(13)]);
+        assert_eq!(doubled.period(), 4);
+        assert_eq!(doubled.cycle_length(1 << 40), 1);
+        assert_eq!(doubled.cycle_length(1), 4);
+    }
+
+    #[test]
+    fn test_prime_factors() {
+        assert_eq!(prime_factors(u64::MAX), [3, 5, 17, 257, 641, 65537, 6700417]);
+        assert_eq!(prime_factors((1 << 61) - 1), [(1 << 61) - 1]);
+        assert_eq!(prime_factors((1 << 59) - 1), [179951, 3203431780337]);
+        assert_eq!(prime_factors(1), []);
+    }
+}